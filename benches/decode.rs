@@ -0,0 +1,77 @@
+//! Clean stream decoding throughput, byte by byte versus in bulk, and recovery from frames
+//! failing CRC per resync strategy: `cargo bench`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bincrc_codec::{BinCrc, BinCrcConfig, Resync};
+use bincrc_codec::typenum::U512;
+
+fn clean_stream() -> Vec<u8> {
+    let mut stream = Vec::new();
+    for i in 0..2000usize {
+        let payload = vec![i as u8; i % 400 + 1];
+        stream.extend_from_slice(&BinCrc::<U512>::frame(payload).unwrap());
+    }
+    stream
+}
+
+// Every frame fails CRC, its payload holds no start bytes and is looked through for frames again
+fn corrupt_stream() -> Vec<u8> {
+    let mut stream = Vec::new();
+    for _ in 0..2000 {
+        let mut frame = BinCrc::<U512>::frame([0x55; 400]).unwrap();
+        frame[10] ^= 1;
+        stream.extend_from_slice(&frame);
+    }
+    stream
+}
+
+fn measure<F: FnMut() -> usize>(name: &str, bytes: usize, mut f: F) {
+    let mut runs = 0;
+    let mut frames = 0;
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(2) {
+        frames += black_box(f());
+        runs += 1;
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    println!(
+        "{:<10} {:>8.1} MB/s ({} frames)",
+        name,
+        (bytes * runs) as f64 / elapsed / 1e6,
+        frames / runs
+    );
+}
+
+fn main() {
+    let stream = clean_stream();
+    measure("eat_byte", stream.len(), || {
+        let mut dec = BinCrc::<U512>::new();
+        let mut frames = 0;
+        for b in &stream {
+            dec.eat_byte(*b, &mut |_| frames += 1);
+        }
+        frames
+    });
+    measure("eat_bytes", stream.len(), || {
+        let mut dec = BinCrc::<U512>::new();
+        let mut frames = 0;
+        for chunk in stream.chunks(64) {
+            dec.eat_bytes(chunk, &mut |_| frames += 1);
+        }
+        frames
+    });
+
+    let stream = corrupt_stream();
+    for &(name, resync) in &[("byte-wise", Resync::StartByte), ("next-start", Resync::NextStartByte)] {
+        measure(name, stream.len(), || {
+            let mut dec = BinCrc::<U512>::with_config(BinCrcConfig { resync, ..Default::default() });
+            let mut frames = 0;
+            for chunk in stream.chunks(64) {
+                dec.eat_bytes(chunk, &mut |_| frames += 1);
+            }
+            frames
+        });
+    }
+}
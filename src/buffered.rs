@@ -0,0 +1,142 @@
+use std::collections::VecDeque;
+
+use generic_array::ArrayLength;
+
+use crate::{BinCrcDecoder, DynLen, LenField};
+
+/// Decoder that queues frames as bytes are pushed, for passing them on without a callback,
+/// e.g. over a channel.
+pub struct BufferedDecoder<N: ArrayLength<u8>, L: LenField = DynLen> {
+    decoder: BinCrcDecoder<N, L>,
+    frames: VecDeque<Vec<u8>>,
+    max_frames: Option<usize>,
+    // Full queue stops push instead of dropping frames
+    backpressure: bool,
+    frames_dropped: u64
+}
+
+impl<N: ArrayLength<u8>, L: LenField> BufferedDecoder<N, L> {
+    pub fn new(decoder: BinCrcDecoder<N, L>) -> Self {
+        BufferedDecoder { decoder, frames: VecDeque::new(), max_frames: None, backpressure: false, frames_dropped: 0 }
+    }
+
+    /// Queues at most `max_frames` frames, frames decoded while the queue is full are dropped
+    /// and counted by `frames_dropped`, see `set_backpressure` for keeping them instead. `None`
+    /// lets the queue grow without bound.
+    pub fn set_max_frames(&mut self, max_frames: Option<usize>) {
+        self.max_frames = max_frames;
+    }
+
+    /// With a limit set by `set_max_frames`, a full queue makes `push` stop taking bytes
+    /// instead of dropping frames, so that a slow consumer throttles the link. Bytes completing
+    /// more than one frame at once may still take the queue past the limit, nothing is dropped.
+    pub fn set_backpressure(&mut self, enabled: bool) {
+        self.backpressure = enabled;
+    }
+
+    /// Decodes `bytes`, queueing the frames they complete. Returns how many bytes were taken,
+    /// fewer than `bytes.len()` only with `set_backpressure` once the queue is full, the rest
+    /// is to be pushed again after frames are popped.
+    pub fn push(&mut self, bytes: &[u8]) -> usize {
+        let (frames, max_frames, dropped) = (&mut self.frames, self.max_frames, &mut self.frames_dropped);
+        let max = match max_frames {
+            Some(max) if self.backpressure => max,
+            _ => {
+                self.decoder.eat_bytes(bytes, &mut |f| {
+                    if max_frames.is_some_and(|max| frames.len() >= max) {
+                        *dropped += 1;
+                    } else {
+                        frames.push_back(Vec::from(f));
+                    }
+                });
+                return bytes.len();
+            }
+        };
+        // Byte by byte, so that not a byte more than needed to fill the queue is taken
+        for (i, b) in bytes.iter().enumerate() {
+            if frames.len() >= max {
+                return i;
+            }
+            self.decoder.eat_byte(*b, &mut |f| frames.push_back(Vec::from(f)));
+        }
+        bytes.len()
+    }
+
+    /// Oldest queued frame.
+    pub fn pop_frame(&mut self) -> Option<Vec<u8>> {
+        self.frames.pop_front()
+    }
+
+    /// Number of frames queued.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Frames dropped for a full queue, see `set_max_frames`.
+    pub fn frames_dropped(&self) -> u64 {
+        self.frames_dropped
+    }
+
+    pub fn decoder(&self) -> &BinCrcDecoder<N, L> {
+        &self.decoder
+    }
+
+    /// Queued frames are dropped.
+    pub fn into_inner(self) -> BinCrcDecoder<N, L> {
+        self.decoder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::U64;
+
+    #[test]
+    fn queued_frames() {
+        let stream: Vec<u8> = (1..=5u8).flat_map(|i| BinCrc::<U64>::frame([i; 4]).unwrap()).collect();
+        let mut dec = BufferedDecoder::new(BinCrc::<U64>::new());
+        dec.push(&stream[..7]);
+        assert!(dec.is_empty());
+        dec.push(&stream[7..]);
+        assert_eq!(dec.len(), 5);
+        for i in 1..=5u8 {
+            assert_eq!(dec.pop_frame(), Some(vec![i; 4]));
+        }
+        assert_eq!(dec.pop_frame(), None);
+
+        dec.set_max_frames(Some(2));
+        dec.push(&stream);
+        assert_eq!(dec.frames_dropped(), 3);
+        assert_eq!(dec.pop_frame(), Some(vec![1; 4]));
+        dec.push(&stream[stream.len() - 9..]);
+        assert_eq!(dec.pop_frame(), Some(vec![2; 4]));
+        assert_eq!(dec.pop_frame(), Some(vec![5; 4]));
+        assert_eq!(dec.decoder().stats().frames, 11);
+    }
+
+    #[test]
+    fn backpressure() {
+        let stream: Vec<u8> = (1..=5u8).flat_map(|i| BinCrc::<U64>::frame([i; 4]).unwrap()).collect();
+        let mut dec = BufferedDecoder::new(BinCrc::<U64>::new());
+        dec.set_max_frames(Some(2));
+        dec.set_backpressure(true);
+        // Stops right after the second frame
+        let mut taken = dec.push(&stream);
+        assert_eq!(taken, 18);
+        assert_eq!(dec.push(&stream[taken..]), 0);
+        let mut popped = Vec::new();
+        while taken < stream.len() {
+            popped.push(dec.pop_frame().unwrap());
+            taken += dec.push(&stream[taken..]);
+        }
+        popped.extend(core::iter::from_fn(|| dec.pop_frame()));
+        assert_eq!(popped, (1..=5u8).map(|i| vec![i; 4]).collect::<Vec<_>>());
+        assert_eq!(dec.frames_dropped(), 0);
+    }
+}
@@ -0,0 +1,174 @@
+use generic_array::ArrayLength;
+
+use crate::{encoder, BinCrcConfig, BinCrcError};
+
+// Chunk index and chunk count in front of every chunk payload
+const CHUNK_HEADER_LEN: usize = 2;
+
+// Frames payload as back to back frames carrying at most mtu bytes each, chunk header included
+pub(crate) fn commit_chunked<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    payload: &[u8],
+    mtu: usize,
+    buf: &mut [u8]
+) -> Result<usize, BinCrcError>
+{
+    if mtu <= CHUNK_HEADER_LEN || payload.is_empty() {
+        return Err(BinCrcError::InvalidLength);
+    }
+    let chunk_len = mtu - CHUNK_HEADER_LEN;
+    let chunks = payload.len().div_ceil(chunk_len);
+    if chunks > u8::MAX as usize {
+        return Err(BinCrcError::TooBig);
+    }
+    let mut written = 0;
+    for (idx, chunk) in payload.chunks(chunk_len).enumerate() {
+        let frame_len = CHUNK_HEADER_LEN + chunk.len();
+        let frame_size = encoder::size_hint::<N>(config, frame_len)?;
+        let out = match buf.get_mut(written .. written + frame_size) {
+            Some(out) => out,
+            None => {
+                let rest = payload[idx * chunk_len..].chunks(chunk_len)
+                    .map(|c| encoder::size_hint::<N>(config, CHUNK_HEADER_LEN + c.len()))
+                    .sum::<Result<usize, _>>()?;
+                return Err(BinCrcError::NotEnoughSpace { required: written + rest, available: buf.len() });
+            }
+        };
+        out[0] = idx as u8;
+        out[1] = chunks as u8;
+        out[CHUNK_HEADER_LEN .. frame_len].copy_from_slice(chunk);
+        written += encoder::commit_in_place::<N>(config, out, 0..frame_len)?;
+    }
+    Ok(written)
+}
+
+/// Puts messages sent with `commit_chunked` back together from decoded frames.
+///
+/// Chunks may come in any order. A chunk that can't belong to the message being collected
+/// (different chunk count or a repeated index) means some chunks were lost, the incomplete
+/// message is dropped with `IncompleteMessage` and collecting starts over from that chunk.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize
+}
+
+#[cfg(feature = "std")]
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one decoded frame payload, `f` gets the message once all of its chunks are in,
+    /// `IncompleteMessage` for a message that won't ever be, or `BadLength` for a frame
+    /// without a valid chunk header.
+    pub fn push<F>(&mut self, frame: &[u8], f: &mut F)
+        where F: FnMut(Result<Vec<u8>, BinCrcError>)
+    {
+        if frame.len() < CHUNK_HEADER_LEN || frame[1] == 0 || frame[0] >= frame[1] {
+            f(Err(BinCrcError::BadLength));
+            return;
+        }
+        let (idx, count) = (frame[0] as usize, frame[1] as usize);
+        if self.received != 0 && (self.chunks.len() != count || self.chunks[idx].is_some()) {
+            self.reset();
+            f(Err(BinCrcError::IncompleteMessage));
+        }
+        if self.received == 0 {
+            self.chunks.resize(count, None);
+        }
+        self.chunks[idx] = Some(frame[CHUNK_HEADER_LEN..].to_vec());
+        self.received += 1;
+        if self.received == count {
+            let message = self.chunks.iter_mut().flat_map(|c| c.take().unwrap()).collect();
+            self.reset();
+            f(Ok(message));
+        }
+    }
+
+    /// Drops the chunks collected so far, e.g. after a timeout.
+    pub fn reset(&mut self) {
+        self.chunks.clear();
+        self.received = 0;
+    }
+
+    /// Whether part of a message is waiting for more chunks.
+    pub fn is_pending(&self) -> bool {
+        self.received != 0
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::U64;
+
+    fn chunks(payload: &[u8], mtu: usize) -> Vec<Vec<u8>> {
+        let mut buf = [0; 512];
+        let written = BinCrc::<U64>::commit_chunked(payload, mtu, &mut buf).unwrap();
+        let mut dec = BinCrc::<U64>::new();
+        let mut frames = Vec::new();
+        for b in &buf[..written] {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        frames
+    }
+
+    #[test]
+    fn chunked_round_trip() {
+        let payload: Vec<u8> = (0..100).collect();
+        let mut frames = chunks(&payload, 22);
+        assert_eq!(frames.len(), 5);
+        assert!(frames.iter().all(|f| f.len() <= 22));
+
+        let mut reassembler = Reassembler::new();
+        frames.swap(1, 3);
+        let mut results = Vec::new();
+        for frame in &frames {
+            reassembler.push(frame, &mut |r| results.push(r));
+        }
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap(), &payload);
+        assert!(!reassembler.is_pending());
+    }
+
+    #[test]
+    fn chunk_lost() {
+        let first: Vec<u8> = (0..50).collect();
+        let second: Vec<u8> = (50..90).collect();
+        let mut frames = chunks(&first, 12);
+        frames.remove(2);
+        frames.extend(chunks(&second, 12));
+        frames.extend(chunks(&[7], 12));
+        let mut reassembler = Reassembler::new();
+        let mut results = Vec::new();
+        for frame in &frames {
+            reassembler.push(frame, &mut |r| results.push(r));
+        }
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], Err(BinCrcError::IncompleteMessage)));
+        assert_eq!(results[1].as_ref().unwrap(), &second);
+        assert_eq!(results[2].as_ref().unwrap(), &[7]);
+
+        // Single chunk message right after the loss is not lost as well
+        let mut frames = chunks(&first, 12);
+        frames.truncate(2);
+        frames.extend(chunks(&[7], 12));
+        let mut results = Vec::new();
+        for frame in &frames {
+            reassembler.push(frame, &mut |r| results.push(r));
+        }
+        assert!(matches!(results[0], Err(BinCrcError::IncompleteMessage)));
+        assert_eq!(results[1].as_ref().unwrap(), &[7]);
+    }
+
+    #[test]
+    fn chunked_errors() {
+        let mut buf = [0; 64];
+        assert!(matches!(BinCrc::<U64>::commit_chunked(&[1; 10], 2, &mut buf), Err(BinCrcError::InvalidLength)));
+        assert!(matches!(BinCrc::<U64>::commit_chunked(&[1; 100], 12, &mut buf), Err(BinCrcError::NotEnoughSpace { .. })));
+        assert!(matches!(BinCrc::<U64>::commit_chunked(&[1; 300], 3, &mut buf), Err(BinCrcError::TooBig)));
+    }
+}
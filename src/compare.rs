@@ -0,0 +1,40 @@
+/// Plain payload comparison.
+pub fn frames_equal(a: &[u8], b: &[u8]) -> bool {
+    a == b
+}
+
+/// Payload comparison that takes the same time wherever the first difference is, for frames
+/// carrying authentication tags. Lengths are not treated as secret.
+pub fn frames_equal_ct(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    core::hint::black_box(diff) == 0
+}
+
+/// 64 bit FNV-1a hash of a payload, stable across runs and platforms, for deduplicating frames.
+pub fn frame_hash(payload: &[u8]) -> u64 {
+    payload.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_and_hash() {
+        assert!(frames_equal(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!frames_equal(&[1, 2, 3], &[1, 2, 4]));
+        assert!(frames_equal_ct(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!frames_equal_ct(&[1, 2, 3], &[0, 2, 3]));
+        assert!(!frames_equal_ct(&[1, 2, 3], &[1, 2]));
+        assert!(frames_equal_ct(&[], &[]));
+
+        assert_eq!(frame_hash(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(frame_hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(frame_hash(&[1, 2]), frame_hash(&[2, 1]));
+    }
+}
@@ -0,0 +1,485 @@
+use crate::{BinCrcError, CrcBackend, TableCrc};
+use crate::crc::{crc32_update, crc8_update};
+
+/// Wire format options shared by the encoder and the decoder, `Default` matches the original format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BinCrcConfig {
+    /// Bytes written before the start byte of every frame, skipped by the decoder.
+    pub preamble: Option<Preamble>,
+    pub length_width: LengthWidth,
+    /// What to do when the buffer is full and yet another byte arrives.
+    pub oversize_recovery: OversizeRecovery,
+    /// Filler byte sent between frames, skipped without counting it as junk.
+    /// Ignored if it is one of the start bytes.
+    pub idle_byte: Option<u8>,
+    /// Repeat the length field after the CRC, the decoder rejects frames where both copies differ.
+    pub trailing_length_check: bool,
+    /// How the decoder finds the next frame after junk.
+    pub resync: Resync,
+    /// Most frames returned by one `Decoder::decode` call, the rest is left for the next call,
+    /// `None` returns everything decodable at once. `Some(1)` favours latency, `None` throughput,
+    /// see also `BinCrcDecoder::set_max_frames_per_decode`. Frames recovered by
+    /// `OversizeRecovery::Rescan` count towards it too, the ones over it stay buffered.
+    pub max_frames_per_decode: Option<usize>,
+    /// Junk bytes in a row tolerated between valid frames before `SyncObserver::gap_exceeded` fires.
+    pub max_junk_run: Option<usize>,
+    /// CRC implementation to use, software one by default. Only used for 16 bit CRCs.
+    pub crc_backend: CrcBackend,
+    /// Size of the CRC field, which also picks the algorithm filling it.
+    pub crc_width: CrcWidth,
+    /// CRC covers the stop byte after the payload too, as some devices do.
+    pub crc_includes_stop: bool,
+    /// Frame failing CRC is skipped as a whole instead of looking for another frame inside it,
+    /// for lock-step protocols where the next frame only starts after the previous one.
+    pub crc_error_skips_frame: bool,
+    pub framing: Framing,
+    /// What the length field counts.
+    pub length_unit: LengthUnit,
+    /// One byte checksum of the payload written after the CRC, for receivers that check both.
+    /// Doesn't apply to `Framing::Delimited`.
+    pub checksum: Option<Checksum8>,
+    /// Stop byte of a frame failing CRC is skipped instead of being tried as a 16 bit start
+    /// byte, which otherwise holds up decoding until as many bytes as the bogus length arrive.
+    pub stop_byte_not_start: bool,
+    /// Bytes skipped after every valid frame before looking for the next one, for half-duplex
+    /// links picking up noise while the line turns around. Counted as idle bytes, not junk.
+    pub settle_bytes: usize,
+    /// Bytes between the start byte and the length field, such as a bus address, not covered
+    /// by the CRC. Taken by `commit_frame_addressed_with` and handed out by `eat_byte_with_address`,
+    /// other ways of committing a frame fail with `InvalidLength` when it is set.
+    /// Doesn't apply to `Framing::Delimited`.
+    pub address_len: usize,
+    /// Most bytes buffered for a frame still being received, from its start byte on. A frame
+    /// that is longer, claimed by a corrupted length field or stalled on the link, is given up
+    /// on and its bytes are searched for the next frame. `None` is limited by `N` only.
+    pub max_partial_bytes: Option<usize>,
+    /// Where the CRC goes, it covers the payload either way. Doesn't apply to `Framing::Delimited`.
+    pub crc_position: CrcPosition,
+    /// What `Encoder::encode` does with a payload too long to be framed.
+    pub oversize_payload: OversizePayload,
+    /// Frames end right after the CRC, checksum or length copy, with no stop byte. Frames are then
+    /// told apart from junk by the start byte, length and CRC alone, `crc_includes_stop` and
+    /// `stop_byte_not_start` don't apply. Doesn't apply to `Framing::Delimited`.
+    pub omit_stop_byte: bool,
+    /// Junk bytes `Resync::CrcAnchored` keeps to look back into once the junk run since the last
+    /// valid frame gets longer than this, older ones are let go. Frames whose start and length
+    /// bytes are older than that can't be recovered any more, in exchange the buffer holds less
+    /// and fewer bytes are checked per byte on long junk bursts. `None` keeps up to half of `N`.
+    pub max_junk_retained: Option<usize>,
+    /// Shortest payload the decoder accepts, in bytes. Shorter frames are rejected as junk even
+    /// if their CRC passes, which random data does more often the shorter it is. 0 and 1 are the
+    /// same, empty frames are never valid.
+    pub min_frame_len: usize,
+    /// Message type field at the beginning of the payload, for `commit_typed` and `eat_byte_typed`.
+    /// Covered by the length and CRC like the rest of the payload.
+    pub type_width: TypeWidth,
+    /// Byte order of a message type field wider than a byte, the length field is always big endian.
+    pub type_byte_order: ByteOrder,
+}
+
+/// Policy for payloads `Encoder::encode` can't frame for `TooBig`, or for `InvalidLength` over 255
+/// bytes with the `len8-only` feature, see `BinCrcConfig::oversize_payload`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OversizePayload {
+    /// Fail with `TooBig`, which ends a tokio `Framed` sink
+    #[default]
+    Error,
+    /// Write nothing, return `Ok` and count the payload as skipped, keeping the sink alive
+    Skip
+}
+
+/// Place of the CRC within a length prefixed frame, see `BinCrcConfig::crc_position`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcPosition {
+    /// Right after the payload
+    #[default]
+    AfterPayload,
+    /// Between the length field and the payload, as some legacy devices send it
+    BeforePayload
+}
+
+/// Size of the CRC field and the algorithm behind it, see `BinCrcConfig::crc_width`. CRCs are
+/// sent big endian like the length field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrcWidth {
+    /// CRC-8/SMBUS, polynomial 0x07, for short frames on links that can't spare a byte
+    Crc8,
+    /// `BinCrcConfig::crc_backend`, CRC-16/XMODEM unless replaced
+    #[default]
+    Crc16,
+    /// CRC-32/ISO-HDLC, the Ethernet and zlib one, for long frames
+    Crc32
+}
+
+impl CrcWidth {
+    /// Bytes taken on the wire.
+    pub fn bytes(self) -> usize {
+        match self {
+            CrcWidth::Crc8 => 1,
+            CrcWidth::Crc16 => 2,
+            CrcWidth::Crc32 => 4
+        }
+    }
+}
+
+/// Width of the message type field, see `BinCrcConfig::type_width`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TypeWidth {
+    /// No type field, the type is always 0
+    None,
+    #[default]
+    U8,
+    U16,
+    U32
+}
+
+impl TypeWidth {
+    /// Bytes taken at the beginning of the payload.
+    pub fn bytes(self) -> usize {
+        match self {
+            TypeWidth::None => 0,
+            TypeWidth::U8 => 1,
+            TypeWidth::U16 => 2,
+            TypeWidth::U32 => 4
+        }
+    }
+}
+
+/// Byte order of multi-byte fields, see `BinCrcConfig::type_byte_order`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    #[default]
+    BigEndian,
+    LittleEndian
+}
+
+impl ByteOrder {
+    // Value of a field of up to 4 bytes
+    pub(crate) fn read(self, bytes: &[u8]) -> u32 {
+        let fold = |value: u32, b: &u8| value << 8 | *b as u32;
+        match self {
+            ByteOrder::BigEndian => bytes.iter().fold(0, fold),
+            ByteOrder::LittleEndian => bytes.iter().rev().fold(0, fold)
+        }
+    }
+
+    // Writes value into all of field, None if it doesn't fit
+    pub(crate) fn write(self, value: u32, field: &mut [u8]) -> Option<()> {
+        if field.len() < 4 && value >> (8 * field.len()) != 0 {
+            return None;
+        }
+        let len = field.len();
+        match self {
+            ByteOrder::BigEndian => field.copy_from_slice(&value.to_be_bytes()[4 - len..]),
+            ByteOrder::LittleEndian => field.copy_from_slice(&value.to_le_bytes()[..len])
+        }
+        Some(())
+    }
+}
+
+/// Secondary checksum, see `BinCrcConfig::checksum`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum8 {
+    /// All payload bytes xored together
+    Xor,
+    /// Wrapping sum of all payload bytes
+    Sum
+}
+
+impl Checksum8 {
+    pub(crate) fn calculate(self, payload: &[u8]) -> u8 {
+        match self {
+            Checksum8::Xor => payload.iter().fold(0, |acc, b| acc ^ b),
+            Checksum8::Sum => payload.iter().fold(0, |acc: u8, b| acc.wrapping_add(*b))
+        }
+    }
+}
+
+/// Unit of the length field, the payload is a whole number of units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthUnit {
+    #[default]
+    Bytes,
+    /// 16 bit words, doubling the range of every length width. Payloads of odd length
+    /// can't be encoded. Doesn't apply to `Framing::Delimited`.
+    Words16
+}
+
+/// How the end of a frame is found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// Length field after the start byte
+    #[default]
+    LengthPrefixed,
+    /// No length field, the frame ends at the stop byte. Start, stop and escape (0x10) bytes
+    /// in the payload and CRC are sent as 0x10 followed by the byte xored with 0x20.
+    /// `length_width`, `trailing_length_check` and `Resync::CrcAnchored` don't apply.
+    /// The payload is unescaped in the decoder buffer, so raw frames passed to
+    /// `eat_byte_with_raw` have it unescaped as well.
+    Delimited
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resync {
+    /// Frames are looked for at start bytes only
+    #[default]
+    StartByte,
+    /// Last resort for links where start and stop bytes get corrupted too: whenever a byte arrives,
+    /// buffered bytes ending with it are also checked for a matching length field and CRC,
+    /// ignoring the start and stop byte values. Checks are bounded to buffer size bytes back
+    /// and 4 CRC computations per byte. Random data passes such a check with roughly 1/65536
+    /// probability once its length field happens to match, so expect occasional false frames
+    /// on long runs of junk. When a stop byte arrives and no frame is found, frames between
+    /// a start byte and it are also tried with each single byte left out, to get over a byte
+    /// inserted or duplicated by the link, see `DecoderStats::insertions_skipped`. That is a CRC
+    /// per byte left out for every start byte whose length field matches, up to 4 of them, so a
+    /// stop byte in junk can cost up to 4 * `N` CRC runs over as many as `N` bytes each.
+    CrcAnchored,
+    /// Same as `StartByte`, and while a frame is being received, a stop byte that completes
+    /// a valid frame beginning at a start byte inside it has the length field of the outer frame
+    /// taken as corrupt: the outer frame is given up on and the inner one decoded right away,
+    /// rather than only once as many bytes as the corrupt length claims arrive and fail CRC.
+    /// Payloads that carry a whole valid frame themselves are mistaken for such a case.
+    /// Costs a decode attempt per start byte buffered whenever a stop byte arrives mid-frame,
+    /// see `DecoderStats::backtracks`.
+    Backtrack,
+    /// Same as `StartByte`, but buffered bytes found invalid are skipped up to the next start
+    /// byte at once, instead of trying every position after them one at a time. Frames found
+    /// are the same, recovery from long runs of buffered junk is faster.
+    NextStartByte
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OversizeRecovery {
+    /// Drop everything buffered and start over from the incoming byte
+    #[default]
+    Reset,
+    /// Drop only the oldest byte and look for frames in the rest of the buffer
+    Rescan
+}
+
+/// Width of the length field following the start byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthWidth {
+    /// 8 bit length (start byte 2) up to 255 bytes, 16 bit (start byte 3) up to 65535 bytes,
+    /// 24 bit (start byte 4) above. The decoder only takes a wider length for values the narrower
+    /// one can't carry, so that every length has one encoding.
+    #[default]
+    Auto,
+    /// Always 8 bit, longer payloads can't be encoded
+    U8,
+    /// Always 16 bit, even for short payloads
+    U16,
+    /// LEB128 varint of 1 to 3 bytes after start byte 2, 7 bits per byte with the high bit set
+    /// on every byte but the last. Frames under 128 bytes look the same as with `U8`.
+    Varint,
+    /// No length field, every payload is this many length units after start byte 2.
+    /// Payloads of any other length can't be encoded, `trailing_length_check` doesn't apply.
+    FixedPayload(u16)
+}
+
+/// `len` idle bytes with `byte` value, usually required by a receiver to lock onto the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preamble {
+    pub byte: u8,
+    pub len: usize
+}
+
+impl BinCrcConfig {
+    /// 16 bit CRC from a lookup table instead of the `crc16` crate, for polynomials it has no
+    /// preset for. Backends are referenced, not owned, by the config so that it stays `Copy`,
+    /// so the table goes into a static:
+    /// `static CRC: TableCrc = TableCrc::new(TABLE, 0, 0);` then `config.with_crc_table(&CRC)`.
+    pub const fn with_crc_table(self, crc: &'static TableCrc) -> Self {
+        BinCrcConfig { crc_backend: CrcBackend(crc), crc_width: CrcWidth::Crc16, ..self }
+    }
+
+    /// Whether frames encoded with one config are decoded with the other, for checking that both
+    /// ends of a link agree, e.g. during a handshake. Only options shaping the frame bytes are
+    /// compared, decoder side ones such as `resync` may differ. Preamble and idle bytes are
+    /// skipped either way, so they are not compared either.
+    pub fn same_wire_format(&self, other: &BinCrcConfig) -> bool {
+        self.length_width == other.length_width
+            && self.trailing_length_check == other.trailing_length_check
+            && self.crc_backend == other.crc_backend
+            && self.crc_width == other.crc_width
+            && self.crc_includes_stop == other.crc_includes_stop
+            && self.framing == other.framing
+            && self.length_unit == other.length_unit
+            && self.checksum == other.checksum
+            && self.address_len == other.address_len
+            && self.crc_position == other.crc_position
+            && self.omit_stop_byte == other.omit_stop_byte
+            && self.type_width == other.type_width
+            && self.type_byte_order == other.type_byte_order
+    }
+
+    // CRC of a frame with this payload
+    pub(crate) fn crc(&self, payload: &[u8]) -> u32 {
+        match self.crc_width {
+            // Backends may do the whole payload at once faster
+            CrcWidth::Crc16 if !self.crc_includes_stop || self.omit_stop_byte => self.crc_backend.0.calculate(payload) as u32,
+            _ => self.crc_resume(self.crc_init(), payload)
+        }
+    }
+
+    // CRC of a frame whose payload is what state was updated with followed by rest
+    pub(crate) fn crc_resume(&self, state: u32, rest: &[u8]) -> u32 {
+        let state = self.crc_update(state, rest);
+        if self.crc_includes_stop && !self.omit_stop_byte {
+            self.crc_finish(self.crc_update(state, &[3]))
+        } else {
+            self.crc_finish(state)
+        }
+    }
+
+    // Running CRC state, of whichever width, before any data was fed
+    pub(crate) fn crc_init(&self) -> u32 {
+        match self.crc_width {
+            CrcWidth::Crc8 => 0,
+            CrcWidth::Crc16 => self.crc_backend.0.init() as u32,
+            CrcWidth::Crc32 => !0
+        }
+    }
+
+    pub(crate) fn crc_update(&self, state: u32, data: &[u8]) -> u32 {
+        match self.crc_width {
+            CrcWidth::Crc8 => crc8_update(state as u8, data) as u32,
+            CrcWidth::Crc16 => self.crc_backend.0.update(state as u16, data) as u32,
+            CrcWidth::Crc32 => crc32_update(state, data)
+        }
+    }
+
+    // CRC value of everything fed so far
+    pub(crate) fn crc_finish(&self, state: u32) -> u32 {
+        match self.crc_width {
+            CrcWidth::Crc8 => state,
+            CrcWidth::Crc16 => self.crc_backend.0.finish(state as u16) as u32,
+            CrcWidth::Crc32 => !state
+        }
+    }
+
+    pub(crate) fn crc_len(&self) -> usize {
+        self.crc_width.bytes()
+    }
+
+    // CRC field at the beginning of bytes
+    pub(crate) fn read_crc(&self, bytes: &[u8]) -> u32 {
+        bytes[..self.crc_len()].iter().fold(0, |crc, b| crc << 8 | *b as u32)
+    }
+
+    // Value of the length field for a payload of frame_len bytes
+    pub(crate) fn len_value(&self, frame_len: usize) -> Result<usize, BinCrcError> {
+        match self.length_unit {
+            LengthUnit::Bytes => Ok(frame_len),
+            LengthUnit::Words16 if frame_len.is_multiple_of(2) => Ok(frame_len / 2),
+            LengthUnit::Words16 => Err(BinCrcError::OddLength)
+        }
+    }
+
+    // Payload bytes per unit of the length field
+    pub(crate) fn len_unit(&self) -> usize {
+        match self.length_unit {
+            LengthUnit::Bytes => 1,
+            LengthUnit::Words16 => 2
+        }
+    }
+
+    pub(crate) fn preamble_len(&self) -> usize {
+        self.preamble.map(|p| p.len).unwrap_or(0)
+    }
+
+    // Lower bound on a frame on the wire: min_frame_len payload bytes after the narrowest
+    // length field, no preamble
+    #[cfg(feature = "std")]
+    pub(crate) fn min_wire_len(&self) -> usize {
+        let payload_len = self.min_frame_len.max(1);
+        if self.framing == Framing::Delimited {
+            return 2 + payload_len + self.crc_len();
+        }
+        let header_len = self.header_len(1);
+        header_len + self.address_len + payload_len + self.trailer_len(header_len - 1)
+    }
+
+    // CRC, optional checksum, optional copy of the length field and the stop byte
+    pub(crate) fn trailer_len(&self, len_field: usize) -> usize {
+        let len_copy = if self.trailing_length_check { len_field } else { 0 };
+        self.crc_len() + self.checksum.is_some() as usize + len_copy + self.stop_len()
+    }
+
+    pub(crate) fn stop_len(&self) -> usize {
+        !self.omit_stop_byte as usize
+    }
+
+    // CRC bytes between the length field and the payload
+    pub(crate) fn crc_ahead_len(&self) -> usize {
+        match self.crc_position {
+            CrcPosition::AfterPayload => 0,
+            CrcPosition::BeforePayload => self.crc_len()
+        }
+    }
+
+    // Checksum byte after the CRC, if any, matches the payload, received is None past the frame end
+    pub(crate) fn checksum_ok(&self, payload: &[u8], received: Option<u8>) -> bool {
+        self.checksum.map(|c| Some(c.calculate(payload)) == received).unwrap_or(true)
+    }
+
+    // Header lengths, start byte and length field without the address, the decoder accepts
+    pub(crate) fn header_lens(&self) -> &'static [usize] {
+        if self.framing == Framing::Delimited {
+            return &[];
+        }
+        if LEN8_ONLY {
+            return match self.length_width {
+                LengthWidth::Auto | LengthWidth::U8 => &[2],
+                LengthWidth::U16 => &[],
+                LengthWidth::Varint => &[2, 3],
+                LengthWidth::FixedPayload(_) => &[1]
+            };
+        }
+        match self.length_width {
+            LengthWidth::Auto => &[2, 3, 4],
+            LengthWidth::U8 => &[2],
+            LengthWidth::U16 => &[3],
+            LengthWidth::Varint => &[2, 3, 4],
+            LengthWidth::FixedPayload(_) => &[1]
+        }
+    }
+
+    // Start byte to use for a frame_len length field value, with 8 and 16 bit widths it is also the header length
+    pub(crate) fn start_byte(&self, frame_len: usize) -> Option<u8> {
+        if LEN8_ONLY && (frame_len > 255 || self.length_width == LengthWidth::U16) {
+            return None;
+        }
+        match self.length_width {
+            LengthWidth::Auto if frame_len <= 255 => Some(2),
+            LengthWidth::Auto | LengthWidth::U16 if frame_len <= u16::MAX as usize => Some(3),
+            LengthWidth::Auto if frame_len as u64 <= MAX_LEN24 => Some(4),
+            LengthWidth::U8 | LengthWidth::Varint if frame_len <= 255 => Some(2),
+            LengthWidth::Varint if frame_len <= u16::MAX as usize => Some(2),
+            LengthWidth::Auto | LengthWidth::U16 | LengthWidth::U8 | LengthWidth::Varint => None,
+            LengthWidth::FixedPayload(len) if frame_len == len as usize => Some(2),
+            LengthWidth::FixedPayload(_) => None
+        }
+    }
+
+    // Start byte and length field for a frame_len length field value
+    pub(crate) fn header_len(&self, frame_len: usize) -> usize {
+        match self.length_width {
+            LengthWidth::Varint => 1 + varint_len(frame_len),
+            LengthWidth::FixedPayload(_) => 1,
+            _ => self.start_byte(frame_len).unwrap_or(3) as usize
+        }
+    }
+}
+
+pub(crate) const MAX_VARINT_LEN: usize = 3;
+
+// Largest 24 bit length, as u64 as it doesn't fit into a 16 bit usize
+pub(crate) const MAX_LEN24: u64 = 0xFF_FFFF;
+
+pub(crate) const LEN8_ONLY: bool = cfg!(feature = "len8-only");
+
+pub(crate) fn varint_len(value: usize) -> usize {
+    1 + (value >= 1 << 7) as usize + (value >= 1 << 14) as usize
+}
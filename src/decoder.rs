@@ -0,0 +1,3865 @@
+use generic_array::{GenericArray, ArrayLength};
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ops::Range;
+
+#[cfg(feature = "std")]
+use tokio_util::codec::{Encoder, Decoder};
+#[cfg(feature = "std")]
+use bytes::{Buf, Bytes, BytesMut};
+#[cfg(feature = "std")]
+use crate::encoder::EncodeIter;
+#[cfg(feature = "allocator-api")]
+use alloc::{alloc::Allocator, vec::Vec as AllocVec};
+
+use crate::{chunk, delimited, encoder, frame, frame_hash, BinCrcConfig, Framing, BinCrcError, DecoderStats, LengthWidth, OversizeRecovery, Resync};
+use crate::config::{LEN8_ONLY, MAX_LEN24};
+use crate::frame::{Anchored, Parsed, Varint};
+use crate::handler::{FrameHandler, HandlerResult, MAX_HANDLERS};
+use crate::observer::{DecoderEvent, SyncObserver, CRC_FAILURE_RUN};
+use crate::queue::QueueConsumer;
+use crate::len::{DynLen, LenField};
+
+// Boxed where there is an allocator, so that the handler can own its state
+#[cfg(feature = "std")]
+type EventHandler = Box<dyn FnMut(DecoderEvent) + Send>;
+#[cfg(not(feature = "std"))]
+type EventHandler = &'static mut (dyn FnMut(DecoderEvent) + Send);
+
+// Candidate frames failing CRC that CRC-anchored resync looks at per byte before giving up
+const MAX_CRC_CHECKS: usize = 4;
+
+// Most frames Decoder::decode reserves room for up front, more than that grow the vector as usual
+#[cfg(feature = "std")]
+const MAX_PREALLOCATED_FRAMES: usize = 64;
+
+/// Streaming decoder, frames are reassembled in a buffer of `N` bytes.
+///
+/// There is no notion of time, state only changes as bytes are fed in. Feeding can stop and
+/// resume at any point, for example while the link is being reconfigured, and a partly
+/// received frame is completed by the bytes that follow.
+pub struct BinCrcDecoder<N: ArrayLength<u8>, L: LenField = DynLen> {
+    buffer: GenericArray<u8, N>,
+    read_idx: usize,
+    write_idx: usize,
+    bytes_left: usize,
+    // Bytes below are already part of emitted frames, CRC-anchored resync doesn't look there
+    anchor_floor: usize,
+    config: BinCrcConfig,
+    handlers: [Option<(u8, &'static dyn FrameHandler)>; MAX_HANDLERS],
+    observer: Option<&'static dyn SyncObserver>,
+    event_handler: Option<EventHandler>,
+    // Junk bytes dropped since the last valid frame
+    junk_run: usize,
+    // Frames failing CRC since the last valid frame
+    crc_failures: usize,
+    // Hash and length of the frame expected to come back
+    echo: Option<(u64, usize)>,
+    // Payloads that are control frames, see eat_byte_with_control
+    control_filter: Option<fn(&[u8]) -> bool>,
+    // Bytes eaten since creation or reset, buffer[write_idx] is at this stream offset
+    position: u64,
+    // Stream offset of the stop byte of the last frame failing CRC
+    failed_stop: Option<u64>,
+    // Bytes still to skip after the last frame, see BinCrcConfig::settle_bytes
+    settle_left: usize,
+    // Most bytes ever buffered at once, see max_occupancy
+    max_occupancy: usize,
+    // CRC over the payload of the frame being received, see running_crc
+    crc_run: Option<CrcRun>,
+    // Copy of the payload of the last frame emitted and its length, see last_frame
+    #[cfg(feature = "last-frame")]
+    last_frame: GenericArray<u8, N>,
+    #[cfg(feature = "last-frame")]
+    last_frame_len: Option<usize>,
+    stats: DecoderStats,
+    _len: PhantomData<L>
+}
+
+// Running CRC state over the first covered payload bytes of the frame at stream offset frame_start
+#[derive(Clone, Copy)]
+struct CrcRun {
+    frame_start: u64,
+    covered: usize,
+    state: u32
+}
+
+/// Frame that passed start/length/stop checks but failed CRC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuspectFrame<'a> {
+    pub payload: &'a [u8],
+    /// CRC computed over the received payload, narrower ones of `CrcWidth` zero extended
+    pub expected_crc: u32,
+    /// CRC as it was received on the wire
+    pub received_crc: u32,
+}
+
+// Everything the decode loop can report, eat_byte* variants pick what they care about
+trait Sink {
+    // raw is the whole frame as received, from start byte to stop byte, offsets is where raw is in the stream
+    fn frame(&mut self, payload: &[u8], raw: &[u8], offsets: Range<u64>);
+
+    // Valid frames the sink can't take are dropped as junk
+    fn accepts(&self, _payload: &[u8]) -> bool { true }
+
+    // Frames failing only CRC are treated as junk unless the sink wants them
+    fn wants_suspect(&self) -> bool { false }
+    fn suspect(&mut self, _frame: SuspectFrame) {}
+}
+
+struct FrameSink<'f, F>(&'f mut F);
+
+impl<'f, F: FnMut(&[u8])> Sink for FrameSink<'f, F> {
+    fn frame(&mut self, payload: &[u8], _raw: &[u8], _offsets: Range<u64>) { (self.0)(payload) }
+}
+
+struct RawSink<'f, F>(&'f mut F);
+
+impl<'f, F: FnMut(&[u8], &[u8])> Sink for RawSink<'f, F> {
+    fn frame(&mut self, payload: &[u8], raw: &[u8], _offsets: Range<u64>) { (self.0)(payload, raw) }
+}
+
+struct OffsetSink<'f, F>(&'f mut F);
+
+impl<'f, F: FnMut(&[u8], Range<u64>)> Sink for OffsetSink<'f, F> {
+    fn frame(&mut self, payload: &[u8], _raw: &[u8], offsets: Range<u64>) { (self.0)(payload, offsets) }
+}
+
+struct AddressSink<'f, F> {
+    frame: &'f mut F,
+    address_len: usize
+}
+
+impl<'f, F: FnMut(&[u8], &[u8])> Sink for AddressSink<'f, F> {
+    fn frame(&mut self, payload: &[u8], raw: &[u8], _offsets: Range<u64>) {
+        (self.frame)(raw.get(1..1 + self.address_len).unwrap_or(&[]), payload)
+    }
+}
+
+struct TypedSink<'f, F> {
+    frame: &'f mut F,
+    type_len: usize
+}
+
+impl<'f, F: FnMut(&[u8], &[u8])> Sink for TypedSink<'f, F> {
+    fn frame(&mut self, payload: &[u8], _raw: &[u8], _offsets: Range<u64>) {
+        let (msg_type, payload) = payload.split_at(self.type_len);
+        (self.frame)(msg_type, payload)
+    }
+
+    // Frames too short for the type field aren't typed frames
+    fn accepts(&self, payload: &[u8]) -> bool {
+        payload.len() >= self.type_len
+    }
+}
+
+struct ControlSink<'f, 'c, F, C> {
+    frame: &'f mut F,
+    control: &'c mut C,
+    filter: Option<fn(&[u8]) -> bool>
+}
+
+impl<'f, 'c, F, C> Sink for ControlSink<'f, 'c, F, C>
+    where F: FnMut(&[u8]), C: FnMut(&[u8])
+{
+    fn frame(&mut self, payload: &[u8], _raw: &[u8], _offsets: Range<u64>) {
+        match self.filter {
+            Some(is_control) if is_control(payload) => (self.control)(payload),
+            _ => (self.frame)(payload)
+        }
+    }
+}
+
+struct ForensicSink<'f, 's, F, S> {
+    frame: &'f mut F,
+    suspect: &'s mut S
+}
+
+impl<'f, 's, F, S> Sink for ForensicSink<'f, 's, F, S>
+    where F: FnMut(&[u8]), S: FnMut(SuspectFrame)
+{
+    fn frame(&mut self, payload: &[u8], _raw: &[u8], _offsets: Range<u64>) { (self.frame)(payload) }
+    fn wants_suspect(&self) -> bool { true }
+    fn suspect(&mut self, frame: SuspectFrame) { (self.suspect)(frame) }
+}
+
+impl<N: ArrayLength<u8>, L: LenField> Default for BinCrcDecoder<N, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: ArrayLength<u8>, L: LenField> BinCrcDecoder<N, L> {
+    pub fn new() -> Self {
+        Self::with_config(BinCrcConfig::default())
+    }
+
+    /// `config.length_width` is replaced with the one `L` dictates, unless it is `DynLen`.
+    pub fn with_config(config: BinCrcConfig) -> Self {
+        let config = L::config(&config);
+        BinCrcDecoder {
+            buffer: GenericArray::default(),
+            read_idx: 0, write_idx: 0, bytes_left: 0,
+            anchor_floor: 0,
+            config,
+            handlers: [None; MAX_HANDLERS],
+            observer: None,
+            event_handler: None,
+            junk_run: 0,
+            crc_failures: 0,
+            echo: None,
+            control_filter: None,
+            position: 0,
+            failed_stop: None,
+            settle_left: 0,
+            max_occupancy: 0,
+            crc_run: None,
+            #[cfg(feature = "last-frame")]
+            last_frame: GenericArray::default(),
+            #[cfg(feature = "last-frame")]
+            last_frame_len: None,
+            stats: DecoderStats::default(),
+            _len: PhantomData
+        }
+    }
+
+    pub fn stats(&self) -> &DecoderStats {
+        &self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = DecoderStats::default();
+    }
+
+    /// Most bytes the buffer ever held at once, partial frames and junk included. Getting close
+    /// to `N` means frames on this link are about to be lost to overflow.
+    pub fn max_occupancy(&self) -> usize {
+        self.max_occupancy
+    }
+
+    pub fn reset_max_occupancy(&mut self) {
+        self.max_occupancy = 0;
+    }
+
+    /// Drops everything buffered and starts counting stream offsets from 0 again.
+    /// Configuration, handlers, observer, stats and `max_occupancy` are kept.
+    pub fn reset(&mut self) {
+        self.read_idx = 0;
+        self.write_idx = 0;
+        self.bytes_left = 0;
+        self.anchor_floor = 0;
+        self.junk_run = 0;
+        self.crc_failures = 0;
+        self.echo = None;
+        self.position = 0;
+        self.failed_stop = None;
+        self.settle_left = 0;
+        self.crc_run = None;
+        #[cfg(feature = "last-frame")]
+        {
+            self.last_frame_len = None;
+        }
+    }
+
+    /// Payload of the last frame emitted, e.g. to match a response to its request, cleared by
+    /// `reset`. Frames are copied into a second buffer of `N` bytes for this, so with the
+    /// `last-frame` feature every decoder takes twice the memory of its buffer.
+    #[cfg(feature = "last-frame")]
+    pub fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame_len.map(|len| &self.last_frame[..len])
+    }
+
+    /// Number of bytes eaten since creation or the last `reset`.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Routes frames beginning with `start_byte` to `handler`, replacing the previous handler
+    /// for this start byte if any.
+    pub fn register_handler(
+        &mut self,
+        start_byte: u8,
+        handler: &'static dyn FrameHandler
+    ) -> Result<(), BinCrcError>
+    {
+        let slot = match self.handlers.iter().position(|h| matches!(h, Some((b, _)) if *b == start_byte)) {
+            Some(existing) => existing,
+            None => self.handlers.iter().position(|h| h.is_none()).ok_or(BinCrcError::TooManyHandlers)?
+        };
+        self.handlers[slot] = Some((start_byte, handler));
+        Ok(())
+    }
+
+    pub fn set_observer(&mut self, observer: &'static dyn SyncObserver) {
+        self.observer = Some(observer);
+    }
+
+    /// Hands every `DecoderEvent` to `handler`, one place for all the diagnostics.
+    #[cfg(feature = "std")]
+    pub fn set_event_handler(&mut self, handler: impl FnMut(DecoderEvent) + Send + 'static) {
+        self.event_handler = Some(Box::new(handler));
+    }
+
+    /// Hands every `DecoderEvent` to `handler`, one place for all the diagnostics. Without an
+    /// allocator the handler has to outlive the decoder, e.g. a closure and the state it keeps
+    /// placed in a static once at startup.
+    #[cfg(not(feature = "std"))]
+    pub fn set_event_handler(&mut self, handler: &'static mut (dyn FnMut(DecoderEvent) + Send)) {
+        self.event_handler = Some(handler);
+    }
+
+    fn event(&mut self, event: DecoderEvent) {
+        if let Some(handler) = &mut self.event_handler {
+            handler(event);
+        }
+    }
+
+    /// Marks frames whose payload `is_control` accepts as control frames, for in-band flow control
+    /// and the like, see `eat_byte_with_control`. Empty frames aren't decodable, so control frames
+    /// need at least one byte of payload.
+    pub fn set_control_filter(&mut self, is_control: fn(&[u8]) -> bool) {
+        self.control_filter = Some(is_control);
+    }
+
+    /// Changes `BinCrcConfig::max_frames_per_decode` on the fly: `Some(1)` returns from
+    /// `Decoder::decode` as soon as a frame is there, `None` batches everything decodable.
+    pub fn set_max_frames_per_decode(&mut self, max_frames: Option<usize>) {
+        self.config.max_frames_per_decode = max_frames;
+    }
+
+    /// Drops the next frame if it is the same as `raw` (start byte through stop byte), for
+    /// half-duplex links where frames sent are received back. Frames are compared by length
+    /// and `frame_hash`, so that nothing but the hash is kept. The expectation only covers
+    /// the next frame, it is cleared by any frame that arrives.
+    pub fn expect_echo(&mut self, raw: &[u8]) {
+        self.echo = Some((frame_hash(raw), raw.len()));
+    }
+
+    // Payload of delimited frames is escaped in the buffer
+    fn unescape(&mut self, range: Range<usize>) -> Range<usize> {
+        if self.config.framing == Framing::Delimited {
+            delimited::unescape_in_place(&mut self.buffer, range)
+        } else {
+            range
+        }
+    }
+
+    fn take_echo(&mut self, raw: Range<usize>) -> bool {
+        let echo = self.echo.take();
+        self.is_echo(echo, raw)
+    }
+
+    fn is_echo(&self, echo: Option<(u64, usize)>, raw: Range<usize>) -> bool {
+        match echo {
+            Some((hash, len)) => len == raw.len() && frame_hash(&self.buffer[raw]) == hash,
+            None => false
+        }
+    }
+
+    fn dropped(&mut self, count: usize) {
+        self.stats.junk_bytes += count as u64;
+        self.event(DecoderEvent::JunkBytes { count });
+        let run_before = self.junk_run;
+        self.junk_run += count;
+        if let Some(max) = self.config.max_junk_retained {
+            if self.junk_run > max {
+                self.anchor_floor = self.anchor_floor.max(self.read_idx.saturating_sub(max));
+            }
+        }
+        if let (Some(max), Some(observer)) = (self.config.max_junk_run, self.observer) {
+            if run_before <= max && self.junk_run > max {
+                observer.gap_exceeded(self.junk_run);
+            }
+        }
+    }
+
+    // Payload is only kept with the last-frame feature
+    #[cfg_attr(not(feature = "last-frame"), allow(unused_variables))]
+    fn found_frame(&mut self, payload: Range<usize>) {
+        #[cfg(feature = "last-frame")]
+        {
+            self.last_frame[..payload.len()].copy_from_slice(&self.buffer[payload.clone()]);
+            self.last_frame_len = Some(payload.len());
+        }
+        self.stats.frames += 1;
+        self.crc_failures = 0;
+        if self.junk_run != 0 {
+            if let Some(observer) = self.observer {
+                observer.resynced(self.junk_run);
+            }
+            self.event(DecoderEvent::Resynced { dropped: self.junk_run });
+            self.junk_run = 0;
+        }
+    }
+
+    fn handler(&self, start_byte: u8) -> Option<&'static dyn FrameHandler> {
+        self.handlers.iter().flatten().find(|(b, _)| *b == start_byte).map(|(_, h)| *h)
+    }
+
+    fn is_start_byte(&self, byte: u8) -> bool {
+        START_BYTES.contains(&byte) || self.handler(byte).is_some()
+    }
+
+    // Preamble and idle bytes that can't be confused with a frame start
+    fn is_idle_byte(&self, byte: u8) -> bool {
+        let is_preamble = matches!(self.config.preamble, Some(p) if p.byte == byte);
+        (is_preamble || self.config.idle_byte == Some(byte)) && !self.is_start_byte(byte)
+    }
+
+    pub fn config(&self) -> &BinCrcConfig {
+        &self.config
+    }
+
+    /// Whether frames encoded with `other_config` are decoded by this decoder, see
+    /// `BinCrcConfig::same_wire_format`.
+    pub fn config_matches(&self, other_config: &BinCrcConfig) -> bool {
+        self.config.same_wire_format(&L::config(other_config))
+    }
+
+    // Bytes kept for a frame that is not complete yet
+    #[cfg(feature = "std")]
+    pub(crate) fn buffered(&self) -> usize {
+        self.write_idx - self.read_idx
+    }
+
+    pub fn eat_byte<F>(&mut self, byte: u8, f: &mut F)
+        where F: FnMut(&[u8])
+    {
+        self.eat_byte_inner(byte, &mut FrameSink(f), usize::MAX);
+    }
+
+    /// Same as calling `eat_byte` for every byte, but bytes known to belong to the frame being
+    /// received are copied at once, so a clean stream is mostly copied rather than scanned.
+    pub fn eat_bytes<F>(&mut self, bytes: &[u8], f: &mut F)
+        where F: FnMut(&[u8])
+    {
+        let mut sink = FrameSink(f);
+        let mut rest = bytes;
+        while let Some((&byte, tail)) = rest.split_first() {
+            let bulk = self.eat_bulk(rest);
+            if bulk != 0 {
+                rest = &rest[bulk..];
+                continue;
+            }
+            self.eat_byte_inner(byte, &mut sink, usize::MAX);
+            rest = tail;
+        }
+    }
+
+    /// Decodes everything queued with `QueueProducer::push_byte` so far, returns the number of
+    /// frames handed to `f`. This is the consumer side of `ByteQueue`, meant for the main loop.
+    pub fn process<M, F>(&mut self, queue: &mut QueueConsumer<M>, f: &mut F) -> usize
+        where M: ArrayLength<u8>, F: FnMut(&[u8])
+    {
+        let mut frames = 0;
+        loop {
+            let bytes = queue.peek();
+            if bytes.is_empty() {
+                return frames;
+            }
+            self.eat_bytes(bytes, &mut |frame| {
+                frames += 1;
+                f(frame);
+            });
+            let count = bytes.len();
+            queue.consume(count);
+        }
+    }
+
+    // Stores bytes up to, but not including, the one completing the pending frame. Returns
+    // how many were taken, the same eat_byte would do for them is skipped: no decode attempts
+    // are made while bytes_left is more than 1.
+    fn eat_bulk(&mut self, bytes: &[u8]) -> usize {
+        // CRC-anchored and backtracking resync look at every byte
+        if self.bytes_left <= 1 || matches!(self.config.resync, Resync::CrcAnchored | Resync::Backtrack) {
+            return 0;
+        }
+        let pending = self.write_idx - self.read_idx;
+        let budget = self.config.max_partial_bytes.unwrap_or(N::to_usize()).min(N::to_usize());
+        let count = (self.bytes_left - 1).min(bytes.len()).min(budget.saturating_sub(pending));
+        if count == 0 {
+            return 0;
+        }
+        if self.write_idx + count > N::to_usize() {
+            self.compact();
+        }
+        self.buffer[self.write_idx .. self.write_idx + count].copy_from_slice(&bytes[..count]);
+        self.write_idx += count;
+        self.position += count as u64;
+        self.max_occupancy = self.max_occupancy.max(self.write_idx - self.read_idx);
+        self.bytes_left -= count;
+        self.advance_crc();
+        self.check_invariants();
+        count
+    }
+
+    // Feeds payload bytes of the pending frame buffered since the last call to its running CRC
+    fn advance_crc(&mut self) {
+        let payload = match self.pending_payload() {
+            Some(payload) => payload,
+            None => { return; }
+        };
+        let frame_start = self.stream_offset(self.read_idx);
+        let mut run = match self.crc_run {
+            Some(run) if run.frame_start == frame_start => run,
+            _ => CrcRun { frame_start, covered: 0, state: self.config.crc_init() }
+        };
+        let from = payload.start + run.covered;
+        let to = payload.end.min(self.write_idx);
+        if from < to {
+            run.state = self.config.crc_update(run.state, &self.buffer[from..to]);
+            run.covered += to - from;
+        }
+        self.crc_run = Some(run);
+    }
+
+    // Buffer indices of the pending frame's payload, part of which may still be to come
+    fn pending_payload(&self) -> Option<Range<usize>> {
+        let data = &self.buffer[self.read_idx..self.write_idx];
+        if self.bytes_left == 0 || data.is_empty() || self.config.framing == Framing::Delimited || self.handler(data[0]).is_some() {
+            return None;
+        }
+        let len_field = self.pending_len_field(data)?;
+        let crc_ahead = self.config.crc_ahead_len();
+        let start = self.read_idx + 1 + self.config.address_len + len_field + crc_ahead;
+        let end = (self.write_idx + self.bytes_left).checked_sub(self.config.trailer_len(len_field) - crc_ahead)?;
+        Some(start..end.max(start))
+    }
+
+    /// Payload bytes of the frame being received that are already covered by its running CRC,
+    /// with the CRC over just those bytes. `eat_bytes` and `process` update it as they buffer
+    /// payload bytes, so that the CRC of a completed frame is only taken over the bytes after
+    /// them. `None` when no frame is being received or it has no length field.
+    pub fn running_crc(&self) -> Option<(usize, u32)> {
+        let run = self.crc_run.filter(|run| run.frame_start == self.stream_offset(self.read_idx))?;
+        self.pending_payload()?;
+        Some((run.covered, self.config.crc_finish(run.state)))
+    }
+
+    /// Same as `eat_byte`, but frames with valid framing and wrong CRC are handed to `suspect`
+    /// instead of being discarded. Suspect frame is consumed as a whole, scanning continues after it.
+    pub fn eat_byte_with_suspect<F, S>(&mut self, byte: u8, f: &mut F, suspect: &mut S)
+        where F: FnMut(&[u8]), S: FnMut(SuspectFrame)
+    {
+        self.eat_byte_inner(byte, &mut ForensicSink { frame: f, suspect }, usize::MAX);
+    }
+
+    /// Same as `eat_byte`, but `f` gets both the payload and the whole frame as it was on the wire
+    /// (start byte through stop byte).
+    pub fn eat_byte_with_raw<F>(&mut self, byte: u8, f: &mut F)
+        where F: FnMut(&[u8], &[u8])
+    {
+        self.eat_byte_inner(byte, &mut RawSink(f), usize::MAX);
+    }
+
+    /// Same as `eat_byte`, but control frames, see `set_control_filter`, go to `on_control`
+    /// instead of `f`. Other `eat_byte*` variants treat them as regular frames.
+    pub fn eat_byte_with_control<F, C>(&mut self, byte: u8, f: &mut F, on_control: &mut C)
+        where F: FnMut(&[u8]), C: FnMut(&[u8])
+    {
+        let filter = self.control_filter;
+        self.eat_byte_inner(byte, &mut ControlSink { frame: f, control: on_control, filter }, usize::MAX);
+    }
+
+    /// Same as `eat_byte`, but `f` also gets the stream offsets of the whole frame, from its start
+    /// byte through its stop byte, counted the same way as `position`.
+    pub fn eat_byte_with_offsets<F>(&mut self, byte: u8, f: &mut F)
+        where F: FnMut(&[u8], Range<u64>)
+    {
+        self.eat_byte_inner(byte, &mut OffsetSink(f), usize::MAX);
+    }
+
+    /// Same as `eat_byte`, but `f` also gets the `BinCrcConfig::address_len` address bytes of
+    /// the frame, as `f(address, payload)`.
+    pub fn eat_byte_with_address<F>(&mut self, byte: u8, f: &mut F)
+        where F: FnMut(&[u8], &[u8])
+    {
+        let address_len = if self.config.framing == Framing::Delimited { 0 } else { self.config.address_len };
+        self.eat_byte_inner(byte, &mut AddressSink { frame: f, address_len }, usize::MAX);
+    }
+
+    /// Same as `eat_byte`, but the message type field of `BinCrcConfig::type_width` is split off
+    /// the payload, as `f(msg_type, payload)`, see `commit_typed`. Frames shorter than the field
+    /// are dropped as junk, a steady `DecoderStats::junk_bytes` count with valid frames on the
+    /// link means the two ends disagree on the width.
+    pub fn eat_byte_typed<F>(&mut self, byte: u8, f: &mut F)
+        where F: FnMut(u32, &[u8])
+    {
+        let order = self.config.type_byte_order;
+        self.eat_byte_typed_raw(byte, &mut |msg_type, payload| f(order.read(msg_type), payload));
+    }
+
+    /// Same as `eat_byte_typed`, with the message type field as it was received.
+    pub fn eat_byte_typed_raw<F>(&mut self, byte: u8, f: &mut F)
+        where F: FnMut(&[u8], &[u8])
+    {
+        let type_len = self.config.type_width.bytes();
+        self.eat_byte_inner(byte, &mut TypedSink { frame: f, type_len }, usize::MAX);
+    }
+
+    /// Copies the payload of a frame completed by `byte` into `out` and returns its length,
+    /// `NotEnoughSpace` if it doesn't fit, in which case the frame is lost. At most one frame is
+    /// returned per call, others completed at the same time are returned by the following calls.
+    pub fn read_frame_into(&mut self, byte: u8, out: &mut [u8]) -> Option<Result<usize, BinCrcError>> {
+        let mut result = None;
+        self.eat_byte_inner(byte, &mut FrameSink(&mut |frame: &[u8]| {
+            result = Some(match out.get_mut(..frame.len()) {
+                Some(out) => {
+                    out.copy_from_slice(frame);
+                    Ok(frame.len())
+                },
+                None => Err(BinCrcError::NotEnoughSpace { required: frame.len(), available: out.len() })
+            });
+        }), 1);
+        result
+    }
+
+    /// Replaces everything buffered with `bytes`, which are then pending at buffer index 0.
+    /// For driving the decoder state machine in tests, panics if `bytes` don't fit.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn set_buffer(&mut self, bytes: &[u8]) {
+        assert!(bytes.len() <= N::to_usize(), "more bytes than the buffer holds");
+        self.buffer[..bytes.len()].copy_from_slice(bytes);
+        self.read_idx = 0;
+        self.write_idx = bytes.len();
+        self.bytes_left = 0;
+        self.anchor_floor = 0;
+        self.position += bytes.len() as u64;
+    }
+
+    /// Bytes buffered and not consumed yet.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn pending(&self) -> &[u8] {
+        &self.buffer[self.read_idx..self.write_idx]
+    }
+
+    /// One decode attempt at the read position, without changing any state.
+    #[cfg(any(test, feature = "test-support"))]
+    pub fn decode_step(&self) -> DecodeResult {
+        self.decode_frame(self.read_idx, self.write_idx - self.read_idx)
+    }
+
+    /// How many more bytes the frame being received needs at least, along with the part of the
+    /// frame they belong to. `None` while looking for a frame.
+    pub fn bytes_needed(&self) -> Option<BytesNeeded> {
+        use BytesNeeded::*;
+        let data = &self.buffer[self.read_idx..self.write_idx];
+        let needed = self.bytes_left;
+        if needed == 0 || data.is_empty() {
+            return None;
+        }
+        // Custom and delimited frames have no length field to go by
+        if self.config.framing == Framing::Delimited || self.handler(data[0]).is_some() {
+            return Some(NeedPayload(needed));
+        }
+        let len_start = 1 + self.config.address_len;
+        let len_field = match self.pending_len_field(data) {
+            Some(len_field) => len_field,
+            None => { return Some(NeedLength(needed)); }
+        };
+        let crc_ahead = self.config.crc_ahead_len();
+        if data.len() < len_start + len_field + crc_ahead {
+            Some(NeedTrailer(needed))
+        } else if needed > self.config.trailer_len(len_field) - crc_ahead {
+            Some(NeedPayload(needed))
+        } else {
+            Some(NeedTrailer(needed))
+        }
+    }
+
+    // Size of the length field of the pending frame in data, None until all of it is buffered
+    fn pending_len_field(&self, data: &[u8]) -> Option<usize> {
+        let len_start = 1 + self.config.address_len;
+        let len_field = match self.config.length_width {
+            LengthWidth::Varint => match frame::read_varint(data.get(len_start..).unwrap_or(&[])) {
+                Varint::Value(_, len_field) => len_field,
+                _ => { return None; }
+            },
+            LengthWidth::FixedPayload(_) => 0,
+            _ => data[0] as usize - 1
+        };
+        if data.len() < len_start + len_field {
+            None
+        } else {
+            Some(len_field)
+        }
+    }
+
+    /// Whether the next `poll_frame` would return a frame, without consuming anything.
+    /// Junk, settle bytes and an expected echo in front of the frame are looked past, the same
+    /// way decoding does.
+    pub fn has_frame(&self) -> bool {
+        // Same walk as scan with poll_frame's sink, on copies of the state scan changes
+        let mut start = self.read_idx;
+        let mut settle_left = self.settle_left;
+        let mut failed_stop = self.failed_stop;
+        let mut echo = self.echo;
+        while start < self.write_idx {
+            let settling = settle_left > 0;
+            if settling || self.is_idle_byte(self.buffer[start]) {
+                settle_left -= settling as usize;
+                start += 1;
+                continue;
+            }
+            if failed_stop.is_some() && failed_stop == Some(self.stream_offset(start)) {
+                failed_stop = None;
+                start += 1;
+                continue;
+            }
+            match self.decode_frame(start, self.write_idx - start) {
+                DecodeResult::NeedMoreBytes(_) => { return false; },
+                DecodeResult::InvalidData => { start += self.invalid_skip(start); },
+                DecodeResult::Oversize { .. } => { start += 1; },
+                DecodeResult::Consumed(count, _) => {
+                    if !self.is_echo(echo.take(), start..start + count) {
+                        return true;
+                    }
+                    start += count;
+                    settle_left = self.config.settle_bytes;
+                },
+                DecodeResult::CrcMismatch { count, .. } => {
+                    if self.config.crc_error_skips_frame {
+                        start += count;
+                    } else {
+                        failed_stop = self.failed_stop_after(start, count).or(failed_stop);
+                        start += 1;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Same as `eat_byte`, but only frames that are valid UTF-8 are handed to `f`. Returns
+    /// `InvalidUtf8` if a frame completed by this byte is not, such frames are consumed anyway.
+    pub fn decode_text<F>(&mut self, byte: u8, f: &mut F) -> Result<(), BinCrcError>
+        where F: FnMut(&str)
+    {
+        let mut invalid = false;
+        self.eat_byte(byte, &mut |frame| match core::str::from_utf8(frame) {
+            Ok(text) => f(text),
+            Err(_) => invalid = true
+        });
+        if invalid {
+            Err(BinCrcError::InvalidUtf8)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Returns the number of frames emitted, scanning stops after max_frames
+    fn eat_byte_inner<S: Sink>(&mut self, byte: u8, sink: &mut S, max_frames: usize) -> usize {
+        //rprintln!("\n\neat: {:02x}", byte);
+        let mut rescued = 0;
+        // Incoming frame is larger than the buffer
+        if self.write_idx - self.read_idx >= N::to_usize() {
+            match self.config.oversize_recovery {
+                OversizeRecovery::Reset => {
+                    if let Some(observer) = self.observer {
+                        observer.buffer_overflow(&self.buffer[self.read_idx..self.write_idx]);
+                    }
+                    self.event(DecoderEvent::BufferOverflow { discarded: self.write_idx - self.read_idx });
+                    self.dropped(self.write_idx - self.read_idx);
+                    self.write_idx = 1;
+                    self.read_idx = 0;
+                    self.bytes_left = 0;
+                    self.anchor_floor = 0;
+                    self.buffer[0] = byte;
+                    self.position += 1;
+                    self.check_invariants();
+                    return 0;
+                },
+                OversizeRecovery::Rescan => {
+                    // Bytes buffered with fill may already hold complete frames
+                    rescued = self.scan(sink, max_frames);
+                    // Give up only on the frame at read_idx, the rest may still hold good frames.
+                    // Frames over max_frames stay buffered, emitting one always makes room.
+                    while self.write_idx - self.read_idx >= N::to_usize() && rescued < max_frames {
+                        self.read_idx += 1;
+                        self.dropped(1);
+                        rescued += self.scan(sink, max_frames - rescued);
+                    }
+                }
+            }
+        }
+        // Partial frame is over budget, it is junk from its start byte on
+        if let Some(max_partial) = self.config.max_partial_bytes {
+            while self.bytes_left > 0 && self.write_idx - self.read_idx >= max_partial {
+                self.stats.partial_frames_abandoned += 1;
+                self.read_idx += 1;
+                self.dropped(1);
+                self.bytes_left = 0;
+                rescued += self.scan(sink, max_frames - rescued);
+            }
+        }
+        self.store_byte(byte);
+        // Valid frame boundary is potentially found, return till enough bytes arrive to make progress
+        let emitted = if self.bytes_left > 1 {
+            self.bytes_left -= 1;
+            self.check_invariants();
+            let maybe_end = byte == 3 || self.config.omit_stop_byte;
+            if maybe_end && self.config.resync == Resync::Backtrack {
+                self.backtrack(sink, max_frames - rescued)
+            } else {
+                0
+            }
+        } else {
+            self.scan(sink, max_frames - rescued)
+        };
+        // Frames beyond max_frames stay buffered for the next call
+        let room = rescued + emitted < max_frames;
+        if emitted == 0 && room && self.config.resync == Resync::CrcAnchored && self.anchored_search(sink) {
+            return rescued + 1;
+        }
+        rescued + emitted
+    }
+
+    // Gives up on the partial frame if a valid frame inside it ends with the newest byte,
+    // decoding continues from that frame's start byte
+    fn backtrack<S: Sink>(&mut self, sink: &mut S, max_frames: usize) -> usize {
+        let end = self.write_idx;
+        for start in self.read_idx + 1 .. end {
+            if !self.is_start_byte(self.buffer[start]) {
+                continue;
+            }
+            if let DecodeResult::Consumed(count, _) = self.decode_frame(start, end - start) {
+                if start + count == end {
+                    self.stats.backtracks += 1;
+                    self.dropped(start - self.read_idx);
+                    self.read_idx = start;
+                    self.bytes_left = 0;
+                    return self.scan(sink, max_frames);
+                }
+            }
+        }
+        0
+    }
+
+    // Looks for a frame ending with the newest byte by its length field and CRC alone
+    fn anchored_search<S: Sink>(&mut self, sink: &mut S) -> bool {
+        let end = self.write_idx;
+        let (start, range, inserted) = match self.anchored_match() {
+            Some((start, range)) => (start, range, false),
+            None => match self.insertion_match() {
+                Some((start, range)) => (start, range, true),
+                None => { return false; }
+            }
+        };
+        if !sink.accepts(&self.buffer[start + range.start .. start + range.end]) {
+            return false;
+        }
+        // Bytes before the frame are junk now, the frame itself may have been counted as junk
+        if start >= self.read_idx {
+            self.dropped(start - self.read_idx);
+        } else {
+            let counted = self.read_idx - start;
+            self.stats.junk_bytes = self.stats.junk_bytes.saturating_sub(counted as u64);
+            self.junk_run = self.junk_run.saturating_sub(counted);
+        }
+        let payload = start + range.start .. start + range.end;
+        sink.frame(&self.buffer[payload.clone()], &self.buffer[start..end], self.stream_offsets(start..end));
+        self.found_frame(payload);
+        self.stats.crc_anchored_frames += 1;
+        if inserted {
+            self.stats.insertions_skipped += 1;
+        }
+        self.read_idx = end;
+        self.anchor_floor = end;
+        self.bytes_left = 0;
+        self.settle_left = self.config.settle_bytes;
+        self.check_invariants();
+        true
+    }
+
+    // Start and payload range of a frame ending with the newest byte
+    fn anchored_match(&self) -> Option<(usize, Range<usize>)> {
+        let end = self.write_idx;
+        let mut crc_checks = 0;
+        for &header_len in self.config.header_lens() {
+            let min_count = header_len + self.config.address_len + 1 + self.config.trailer_len(header_len - 1);
+            let max_count = (end - self.anchor_floor).min(N::to_usize());
+            for count in min_count..=max_count {
+                let start = end - count;
+                match frame::parse_anchored(&self.config, &self.buffer[start..end], header_len) {
+                    Anchored::NoMatch => {},
+                    Anchored::CrcMismatch => {
+                        crc_checks += 1;
+                        if crc_checks == MAX_CRC_CHECKS {
+                            return None;
+                        }
+                    },
+                    Anchored::Frame(range) => { return Some((start, range)); }
+                }
+            }
+        }
+        None
+    }
+
+    // Frame between a start byte and the newest byte, which must be a stop byte, with one extra
+    // byte inserted somewhere in between. The frame is moved one byte up over the extra byte.
+    fn insertion_match(&mut self) -> Option<(usize, Range<usize>)> {
+        let end = self.write_idx;
+        if end == 0 || self.config.omit_stop_byte || self.buffer[end - 1] != 3 {
+            return None;
+        }
+        let mut crc_starts = 0;
+        for &header_len in self.config.header_lens() {
+            let start_byte = match self.config.length_width {
+                LengthWidth::Varint | LengthWidth::FixedPayload(_) => 2,
+                _ => header_len as u8
+            };
+            let min_count = header_len + self.config.address_len + 2 + self.config.trailer_len(header_len - 1);
+            let max_count = (end - self.anchor_floor).min(N::to_usize());
+            for count in min_count..=max_count {
+                let start = end - count;
+                if self.buffer[start] != start_byte {
+                    continue;
+                }
+                match self.skip_insertion(start, end, header_len) {
+                    Anchored::NoMatch => {},
+                    // Every byte left out costs a CRC once the length matches, so only a few
+                    // start bytes get that far
+                    Anchored::CrcMismatch => {
+                        crc_starts += 1;
+                        if crc_starts == MAX_CRC_CHECKS {
+                            return None;
+                        }
+                    },
+                    Anchored::Frame(range) => { return Some((start + 1, range)); }
+                }
+            }
+        }
+        None
+    }
+
+    // Tries leaving out every byte between start and end in turn, keeping the window at start + 1
+    // as the bytes without it. Buffer is restored if none of them makes a frame, CrcMismatch then
+    // tells that some of them got as far as the CRC.
+    fn skip_insertion(&mut self, start: usize, end: usize, header_len: usize) -> Anchored {
+        // Byte left out before the current one
+        let mut held = self.buffer[start];
+        let last = end - start - 2;
+        let mut result = Anchored::NoMatch;
+        for skipped in 1..=last {
+            core::mem::swap(&mut self.buffer[start + skipped], &mut held);
+            match frame::parse_anchored(&self.config, &self.buffer[start + 1..end], header_len) {
+                Anchored::NoMatch => {},
+                Anchored::CrcMismatch => { result = Anchored::CrcMismatch; },
+                frame => { return frame; }
+            }
+        }
+        self.buffer.copy_within(start + 1 ..= start + last, start);
+        self.buffer[start + last] = held;
+        result
+    }
+
+    /// Buffers as many `bytes` as fit without decoding them, returns how many were taken.
+    /// Use `poll_frame` or `drain` to get the frames out afterwards.
+    pub fn fill(&mut self, bytes: &[u8]) -> usize {
+        let free = N::to_usize() - (self.write_idx - self.read_idx);
+        let accepted = bytes.len().min(free);
+        for b in &bytes[..accepted] {
+            self.store_byte(*b);
+        }
+        self.bytes_left = self.bytes_left.saturating_sub(accepted);
+        self.check_invariants();
+        accepted
+    }
+
+    // Caller must ensure there is room for one more pending byte
+    fn store_byte(&mut self, byte: u8) {
+        if self.write_idx >= N::to_usize() {
+            self.compact();
+        }
+        // Save incoming byte
+        self.buffer[self.write_idx] = byte;
+        self.write_idx += 1;
+        self.position += 1;
+        self.max_occupancy = self.max_occupancy.max(self.write_idx - self.read_idx);
+    }
+
+    // Where buffer bytes at range are in the stream
+    fn stream_offsets(&self, range: Range<usize>) -> Range<u64> {
+        self.stream_offset(range.start) .. self.stream_offset(range.end)
+    }
+
+    fn stream_offset(&self, idx: usize) -> u64 {
+        self.position - (self.write_idx - idx) as u64
+    }
+
+    // Move part of the frame in the tail to the head (only when frame is wrapping around after junk bytes)
+    fn compact(&mut self) {
+        // CRC-anchored resync needs some of the already skipped bytes as well
+        let keep_from = if self.config.resync == Resync::CrcAnchored {
+            self.anchor_floor.max(self.write_idx - N::to_usize() / 2).min(self.read_idx)
+        } else {
+            self.read_idx
+        };
+        let bytes_kept = self.write_idx - keep_from;
+        unsafe {
+            core::ptr::copy(
+                self.buffer.as_ptr().add(keep_from),
+                self.buffer.as_mut_ptr(),
+                bytes_kept);
+        }
+        self.read_idx -= keep_from;
+        self.write_idx = bytes_kept;
+        self.anchor_floor = 0;
+    }
+
+    // Bytes to drop for invalid data at start, up to whatever the scan has to look at next
+    fn invalid_skip(&self, start: usize) -> usize {
+        if self.config.resync != Resync::NextStartByte {
+            return 1;
+        }
+        self.buffer[start + 1 .. self.write_idx].iter()
+            .position(|b| self.is_start_byte(*b) || self.is_idle_byte(*b))
+            .map_or(self.write_idx - start, |pos| pos + 1)
+    }
+
+    // Stream offset of the stop byte of a frame at start failing CRC, if it is to be skipped
+    fn failed_stop_after(&self, start: usize, count: usize) -> Option<u64> {
+        if self.config.stop_byte_not_start && !self.config.omit_stop_byte {
+            Some(self.stream_offset(start + count - 1))
+        } else {
+            None
+        }
+    }
+
+    // Search for frame boundary when unsynchronised or just check crc and emit valid frames,
+    // stops after max_frames frames or when more bytes are needed. Returns number of frames emitted.
+    // has_frame walks the buffer the same way, keep both in step.
+    fn scan<S: Sink>(&mut self, sink: &mut S, max_frames: usize) -> usize {
+        let mut emitted = 0;
+        while emitted < max_frames {
+            //rprintln!("___");
+            // Preamble and idle bytes can't start a frame, skip them without a decode attempt
+            let settling = self.settle_left > 0;
+            if self.read_idx < self.write_idx && (settling || self.is_idle_byte(self.buffer[self.read_idx])) {
+                self.settle_left -= settling as usize;
+                self.read_idx += 1;
+                self.stats.idle_bytes += 1;
+                continue;
+            }
+            // Known to be a stop byte, not a start byte
+            if self.failed_stop.is_some() && self.failed_stop == Some(self.stream_offset(self.read_idx)) {
+                self.failed_stop = None;
+                self.read_idx += 1;
+                self.dropped(1);
+                continue;
+            }
+            let result = self.decode_frame(self.read_idx, self.write_idx - self.read_idx);
+            self.bytes_left = match result {
+                DecodeResult::NeedMoreBytes(count) => count,
+                _ => 0
+            };
+            match result {
+                DecodeResult::NeedMoreBytes(_) => { break; }, // probably wrong if junk was recognized as frame start and followed by a good frame
+                DecodeResult::InvalidData => {
+                    let count = self.invalid_skip(self.read_idx);
+                    self.read_idx += count;
+                    self.dropped(count);
+                },
+                DecodeResult::Oversize { claimed_len } => {
+                    self.stats.oversize_frames += 1;
+                    if let Some(observer) = self.observer {
+                        observer.frame_dropped(BinCrcError::TooBig);
+                    }
+                    self.event(DecoderEvent::OversizedFrame { claimed_len });
+                    self.read_idx += 1;
+                    self.dropped(1);
+                },
+                DecodeResult::Consumed(count, range) => {
+                    let raw = self.read_idx..self.read_idx + count;
+                    let is_echo = self.take_echo(raw.clone());
+                    let range = self.unescape(range);
+                    if !is_echo && !sink.accepts(&self.buffer[range.clone()]) {
+                        self.read_idx += count;
+                        self.dropped(count);
+                        continue;
+                    }
+                    if !is_echo {
+                        sink.frame(&self.buffer[range.clone()], &self.buffer[raw.clone()], self.stream_offsets(raw));
+                    }
+                    self.read_idx += count;
+                    self.anchor_floor = self.read_idx;
+                    self.settle_left = self.config.settle_bytes;
+                    if is_echo {
+                        self.stats.echoes_suppressed += 1;
+                        self.junk_run = 0;
+                    } else {
+                        self.found_frame(range);
+                        emitted += 1;
+                    }
+                },
+                DecodeResult::CrcMismatch { count, range, expected_crc, received_crc } => {
+                    self.stats.crc_errors += 1;
+                    self.crc_failures += 1;
+                    self.event(DecoderEvent::CrcError { expected: expected_crc, got: received_crc });
+                    if let (CRC_FAILURE_RUN, Some(observer)) = (self.crc_failures, self.observer) {
+                        observer.crc_failure_run(self.crc_failures);
+                    }
+                    if sink.wants_suspect() {
+                        let range = self.unescape(range);
+                        sink.suspect(SuspectFrame {
+                            payload: &self.buffer[range],
+                            expected_crc,
+                            received_crc
+                        });
+                        self.read_idx += count;
+                        self.anchor_floor = self.read_idx;
+                        continue;
+                    }
+                    if let Some(observer) = self.observer {
+                        observer.frame_dropped(BinCrcError::CrcMismatch);
+                    }
+                    if self.config.crc_error_skips_frame {
+                        self.read_idx += count;
+                        self.dropped(count);
+                    } else {
+                        if let Some(offset) = self.failed_stop_after(self.read_idx, count) {
+                            self.failed_stop = Some(offset);
+                        }
+                        self.read_idx += 1;
+                        self.dropped(1);
+                    }
+                },
+            }
+        }
+
+        // Everything consumed, start from the head again, unless skipped bytes are still needed
+        let retained = self.config.resync == Resync::CrcAnchored && self.anchor_floor < self.read_idx;
+        if self.read_idx == self.write_idx && !retained {
+            self.read_idx = 0;
+            self.write_idx = 0;
+            self.anchor_floor = 0;
+        }
+        self.check_invariants();
+        emitted
+    }
+
+    /// Decodes the next complete frame out of the bytes already buffered with `fill`.
+    #[cfg(feature = "std")]
+    pub fn poll_frame(&mut self) -> Option<Vec<u8>> {
+        self.poll_frame_into(|f| Vec::from(f))
+    }
+
+    /// Same as `poll_frame`, with the frame allocated from `alloc`.
+    #[cfg(feature = "allocator-api")]
+    pub fn poll_frame_in<A: Allocator>(&mut self, alloc: A) -> Option<AllocVec<u8, A>> {
+        let mut alloc = Some(alloc);
+        self.poll_frame_into(|f| {
+            let mut frame = AllocVec::new_in(alloc.take().expect("only one frame is polled"));
+            frame.extend_from_slice(f);
+            frame
+        })
+    }
+
+    /// Same as `drain`, with the frames allocated from `alloc`.
+    #[cfg(feature = "allocator-api")]
+    pub fn drain_in<'a, A: Allocator + Clone + 'a>(&'a mut self, alloc: A) -> impl Iterator<Item = AllocVec<u8, A>> + 'a {
+        core::iter::from_fn(move || self.poll_frame_in(alloc.clone()))
+    }
+
+    #[cfg(any(feature = "std", feature = "allocator-api"))]
+    fn poll_frame_into<T, M: FnMut(&[u8]) -> T>(&mut self, mut make: M) -> Option<T> {
+        let mut frame = None;
+        loop {
+            if self.scan(&mut FrameSink(&mut |f: &[u8]| frame = Some(make(f))), 1) == 1 {
+                return frame;
+            }
+            if self.write_idx - self.read_idx < N::to_usize() {
+                return None;
+            }
+            // Buffer is full and still not enough bytes, this frame won't ever fit
+            self.read_idx += 1;
+            self.dropped(1);
+        }
+    }
+
+    /// Yields every complete frame currently buffered, partial frame at the end is kept.
+    #[cfg(feature = "std")]
+    pub fn drain(&mut self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        core::iter::from_fn(move || self.poll_frame())
+    }
+
+    #[inline]
+    fn check_invariants(&self) {
+        debug_assert!(self.read_idx <= self.write_idx);
+        debug_assert!(self.write_idx < 2 * N::to_usize());
+    }
+
+    // Attempt to decode data_len bytes at start, ranges in the result are buffer indices
+    fn decode_frame(&self, start: usize, data_len: usize) -> DecodeResult
+    {
+        use DecodeResult::*;
+        // Only bytes received count, whatever the caller asked for, stale ones past write_idx
+        // could otherwise complete a frame
+        let start = start.min(self.write_idx);
+        let data_len = data_len.min(self.write_idx - start);
+        if data_len > 0 {
+            if let Some(handler) = self.handler(self.buffer[start]) {
+                return self.decode_custom(handler, start, data_len);
+            }
+        }
+        let data = &self.buffer[start..start + data_len];
+        let offset = |r: Range<usize>| {
+            debug_assert!(r.start <= r.end && r.end <= data_len);
+            Range { start: r.start + start, end: r.end + start }
+        };
+        let crc_prefix = match self.crc_run {
+            Some(run) if run.frame_start == self.stream_offset(start) => Some((run.covered, run.state)),
+            _ => None
+        };
+        match frame::parse_resuming::<L>(&self.config, N::to_usize(), data, crc_prefix) {
+            Parsed::NeedMoreBytes(count) => NeedMoreBytes(count),
+            Parsed::TooBig { claimed_len } => Oversize { claimed_len },
+            Parsed::Invalid(_) => InvalidData,
+            Parsed::Frame { count, range } => Consumed(count, offset(range)),
+            Parsed::CrcMismatch { count, range, expected_crc, received_crc } => {
+                CrcMismatch { count, range: offset(range), expected_crc, received_crc }
+            }
+        }
+    }
+
+    fn decode_custom(&self, handler: &dyn FrameHandler, start: usize, data_len: usize) -> DecodeResult {
+        let data = &self.buffer[start..start + data_len];
+        match handler.decode_frame(data) {
+            HandlerResult::NeedMoreBytes(count) => {
+                // Frame that can't fit into the buffer is junk, same as for built-in formats
+                if count == 0 {
+                    return DecodeResult::InvalidData;
+                }
+                // Count comes from outside, so the sum may not even fit into usize
+                match data_len.checked_add(count) {
+                    Some(total) if total > N::to_usize() => { return DecodeResult::Oversize { claimed_len: total }; },
+                    Some(_) => {},
+                    None => { return DecodeResult::InvalidData; }
+                }
+                DecodeResult::NeedMoreBytes(count)
+            },
+            HandlerResult::InvalidData => DecodeResult::InvalidData,
+            HandlerResult::Consumed(count, range) => {
+                if count == 0 || count > data_len || range.start > range.end || range.end > count {
+                    return DecodeResult::InvalidData;
+                }
+                DecodeResult::Consumed(
+                    count,
+                    Range { start: start + range.start, end: start + range.end }
+                )
+            }
+        }
+    }
+
+    /// Checks that `buf` holds exactly one valid frame and returns the payload range within it.
+    pub fn verify_frame(buf: &[u8]) -> Result<Range<usize>, BinCrcError> {
+        Self::verify_frame_with(&BinCrcConfig::default(), buf)
+    }
+
+    pub fn verify_frame_with(config: &BinCrcConfig, buf: &[u8]) -> Result<Range<usize>, BinCrcError> {
+        frame::verify::<L>(&L::config(config), N::to_usize(), buf)
+    }
+
+    /// Whether the CRC of the single frame in `framed` matches. Errors are for framing problems
+    /// only, a CRC or checksum mismatch is `Ok(false)`.
+    pub fn check_crc(framed: &[u8]) -> Result<bool, BinCrcError> {
+        Self::check_crc_with(&BinCrcConfig::default(), framed)
+    }
+
+    pub fn check_crc_with(config: &BinCrcConfig, framed: &[u8]) -> Result<bool, BinCrcError> {
+        frame::check_crc::<L>(&L::config(config), N::to_usize(), framed)
+    }
+
+    /// Decodes every frame in `buf`, e.g. a UDP datagram, holding whole frames back to back.
+    /// Nothing is kept between calls. Returns the number of frames, or an error for the first
+    /// frame that is invalid or cut short, the frames before it were given to `f` already.
+    pub fn decode_datagram<F>(buf: &[u8], f: &mut F) -> Result<usize, BinCrcError>
+        where F: FnMut(&[u8])
+    {
+        Self::decode_datagram_with(&BinCrcConfig::default(), buf, f)
+    }
+
+    pub fn decode_datagram_with<F>(config: &BinCrcConfig, buf: &[u8], f: &mut F) -> Result<usize, BinCrcError>
+        where F: FnMut(&[u8])
+    {
+        let config = L::config(config);
+        let mut frames = 0;
+        let mut rest = buf;
+        while !rest.is_empty() {
+            match frame::parse::<L>(&config, N::to_usize(), rest) {
+                Parsed::NeedMoreBytes(_) => { return Err(BinCrcError::UnexpectedEof); },
+                Parsed::Invalid(e) => { return Err(e); },
+                Parsed::TooBig { .. } => { return Err(BinCrcError::TooBig); },
+                Parsed::CrcMismatch { .. } => { return Err(BinCrcError::CrcMismatch); },
+                Parsed::Frame { count, range } => {
+                    if config.framing == Framing::Delimited {
+                        // Unescaped in a copy, buf is borrowed
+                        let mut frame: GenericArray<u8, N> = GenericArray::default();
+                        frame[..count].copy_from_slice(&rest[..count]);
+                        let range = delimited::unescape_in_place(&mut frame, range);
+                        f(&frame[range]);
+                    } else {
+                        f(&rest[range]);
+                    }
+                    frames += 1;
+                    rest = &rest[count..];
+                }
+            }
+        }
+        Ok(frames)
+    }
+
+    /// Where the valid frames in `buf` are, start byte through stop byte, e.g. to index a
+    /// capture. Junk and frames failing CRC in between are looked through the same way the
+    /// streaming decoder does, payloads are not copied or unescaped.
+    #[cfg(feature = "std")]
+    pub fn frame_ranges(buf: &[u8]) -> Vec<Range<usize>> {
+        Self::frame_ranges_with(&BinCrcConfig::default(), buf)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn frame_ranges_with(config: &BinCrcConfig, buf: &[u8]) -> Vec<Range<usize>> {
+        let config = L::config(config);
+        let mut ranges = Vec::new();
+        let mut idx = 0;
+        while idx < buf.len() {
+            idx += match frame::parse::<L>(&config, N::to_usize(), &buf[idx..]) {
+                Parsed::Frame { count, .. } => {
+                    ranges.push(idx..idx + count);
+                    count
+                },
+                Parsed::CrcMismatch { count, .. } if config.crc_error_skips_frame => count,
+                _ => 1
+            };
+        }
+        ranges
+    }
+
+    pub fn size_hint(frame_len: usize) -> Result<usize, BinCrcError> {
+        Self::size_hint_with(&BinCrcConfig::default(), frame_len)
+    }
+
+    /// Total of `size_hint` over payloads of `frame_lens` bytes, enough for `commit_frames`, or
+    /// to reserve a `BytesMut` once for a burst of frames `Encoder::encode` writes into it.
+    pub fn size_hint_frames(frame_lens: &[usize]) -> Result<usize, BinCrcError> {
+        Self::size_hint_frames_with(&BinCrcConfig::default(), frame_lens)
+    }
+
+    pub fn size_hint_frames_with(config: &BinCrcConfig, frame_lens: &[usize]) -> Result<usize, BinCrcError> {
+        encoder::size_hint_frames::<N>(&L::config(config), frame_lens.iter().copied())
+    }
+
+    /// `size_hint` for the default config, usable in constants. Where `size_hint` returns
+    /// an error this panics, which fails the build in a const context.
+    pub const fn size_hint_const(frame_len: usize) -> usize {
+        let width = match L::WIDTH {
+            Some(width) => width,
+            None => LengthWidth::Auto
+        };
+        let too_big = (frame_len > N::USIZE && frame_len > 255)
+            || (frame_len > u16::MAX as usize && !matches!(width, LengthWidth::Auto))
+            || frame_len as u64 > MAX_LEN24
+            || (LEN8_ONLY && (frame_len > 255 || matches!(width, LengthWidth::U16)))
+            || (matches!(width, LengthWidth::U8) && frame_len > 255);
+        if too_big {
+            panic!("payload is too big to be framed");
+        }
+        let header_len = match width {
+            LengthWidth::Auto if frame_len <= 255 => 2,
+            LengthWidth::Auto if frame_len <= u16::MAX as usize => 3,
+            LengthWidth::Auto => 4,
+            LengthWidth::U16 => 3,
+            LengthWidth::U8 => 2,
+            // LenField types only fix the widths above
+            LengthWidth::Varint | LengthWidth::FixedPayload(_) => panic!("no LenField has this width")
+        };
+        header_len + frame_len + 3
+    }
+
+    pub fn size_hint_with(config: &BinCrcConfig, frame_len: usize) -> Result<usize, BinCrcError> {
+        encoder::size_hint::<N>(&L::config(config), frame_len)
+    }
+
+    /// Frames `frame` into `buf`, returns the number of bytes written.
+    pub fn commit_frame(
+        frame: &[u8],
+        buf: &mut[u8]
+    ) -> core::result::Result<usize, BinCrcError>
+    {
+        Self::commit_frame_with(&BinCrcConfig::default(), frame, buf)
+    }
+
+    pub fn commit_frame_with(
+        config: &BinCrcConfig,
+        frame: &[u8],
+        buf: &mut[u8]
+    ) -> core::result::Result<usize, BinCrcError>
+    {
+        encoder::commit_frame::<N>(&L::config(config), frame, buf)
+    }
+
+    /// Frames `frame` with `address` between the start byte and the length field, `address` must
+    /// be `BinCrcConfig::address_len` bytes long.
+    pub fn commit_frame_addressed_with(
+        config: &BinCrcConfig,
+        address: &[u8],
+        frame: &[u8],
+        buf: &mut [u8]
+    ) -> Result<usize, BinCrcError>
+    {
+        encoder::commit_frame_addressed::<N>(&L::config(config), address, frame, buf)
+    }
+
+    /// Same as `commit_frame`, but `buf` doesn't have to be initialized, returns the number
+    /// of bytes written, all of them initialized.
+    pub fn commit_frame_uninit(frame: &[u8], buf: &mut [MaybeUninit<u8>]) -> Result<usize, BinCrcError> {
+        Self::commit_frame_uninit_with(&BinCrcConfig::default(), frame, buf)
+    }
+
+    pub fn commit_frame_uninit_with(
+        config: &BinCrcConfig,
+        frame: &[u8],
+        buf: &mut [MaybeUninit<u8>]
+    ) -> Result<usize, BinCrcError>
+    {
+        encoder::commit_frame_uninit::<N>(&L::config(config), frame, buf)
+    }
+
+    /// Frames `payload` with a message type field in front of it, inside the length and CRC,
+    /// returns the number of bytes written. `eat_byte_typed` splits it off again. The field is
+    /// a byte unless `BinCrcConfig::type_width` says otherwise, `InvalidLength` if `msg_type`
+    /// doesn't fit into it.
+    pub fn commit_typed(msg_type: u32, payload: &[u8], buf: &mut [u8]) -> Result<usize, BinCrcError> {
+        Self::commit_typed_with(&BinCrcConfig::default(), msg_type, payload, buf)
+    }
+
+    pub fn commit_typed_with(
+        config: &BinCrcConfig,
+        msg_type: u32,
+        payload: &[u8],
+        buf: &mut [u8]
+    ) -> Result<usize, BinCrcError>
+    {
+        encoder::commit_typed::<N>(&L::config(config), msg_type, payload, buf)
+    }
+
+    /// Frames every one of `frames` back to back, returns the number of bytes written. With
+    /// `LengthWidth::Auto` short and long frames get 8 and 16 bit length fields in the same batch.
+    /// On error `buf` holds the frames before the failing one.
+    pub fn commit_frames(frames: &[&[u8]], buf: &mut [u8]) -> Result<usize, BinCrcError> {
+        Self::commit_frames_with(&BinCrcConfig::default(), frames, buf)
+    }
+
+    pub fn commit_frames_with(
+        config: &BinCrcConfig,
+        frames: &[&[u8]],
+        buf: &mut [u8]
+    ) -> Result<usize, BinCrcError>
+    {
+        encoder::commit_frames::<N>(&L::config(config), frames, buf)
+    }
+
+    /// Splits `payload` into back to back frames of at most `mtu` payload bytes each, two of
+    /// which are taken by a chunk header (index and count) for `Reassembler`. Returns the number
+    /// of bytes written, up to 255 chunks are supported.
+    pub fn commit_chunked(payload: &[u8], mtu: usize, buf: &mut [u8]) -> Result<usize, BinCrcError> {
+        Self::commit_chunked_with(&BinCrcConfig::default(), payload, mtu, buf)
+    }
+
+    pub fn commit_chunked_with(
+        config: &BinCrcConfig,
+        payload: &[u8],
+        mtu: usize,
+        buf: &mut [u8]
+    ) -> Result<usize, BinCrcError>
+    {
+        chunk::commit_chunked::<N>(&L::config(config), payload, mtu, buf)
+    }
+
+    /// Frames a payload that is already in `buf` at `payload`, moving it into place. Returns
+    /// the number of bytes written from the start of `buf`. A separate payload slice can't
+    /// overlap `buf`, this is the way to frame without a copy into another buffer.
+    pub fn commit_in_place(buf: &mut [u8], payload: Range<usize>) -> Result<usize, BinCrcError> {
+        Self::commit_in_place_with(&BinCrcConfig::default(), buf, payload)
+    }
+
+    pub fn commit_in_place_with(
+        config: &BinCrcConfig,
+        buf: &mut [u8],
+        payload: Range<usize>
+    ) -> Result<usize, BinCrcError>
+    {
+        encoder::commit_in_place::<N>(&L::config(config), buf, payload)
+    }
+
+    pub fn commit_str(text: &str, buf: &mut [u8]) -> Result<usize, BinCrcError> {
+        Self::commit_frame(text.as_bytes(), buf)
+    }
+
+    /// Allocating version of `commit_frame`, returns the frame ready to be sent.
+    #[cfg(feature = "std")]
+    pub fn frame<P: AsRef<[u8]>>(payload: P) -> Result<Vec<u8>, BinCrcError> {
+        Self::frame_with(&BinCrcConfig::default(), payload)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn frame_with<P: AsRef<[u8]>>(config: &BinCrcConfig, payload: P) -> Result<Vec<u8>, BinCrcError> {
+        let payload = payload.as_ref();
+        let mut buf = vec![0; Self::size_hint_with(config, payload.len())?];
+        let len = Self::commit_frame_with(config, payload, &mut buf)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Same as `frame`, as `Bytes` that are cheap to clone, e.g. to send one frame to many peers.
+    #[cfg(feature = "std")]
+    pub fn frame_bytes<P: AsRef<[u8]>>(payload: P) -> Result<Bytes, BinCrcError> {
+        Self::frame_bytes_with(&BinCrcConfig::default(), payload)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn frame_bytes_with<P: AsRef<[u8]>>(config: &BinCrcConfig, payload: P) -> Result<Bytes, BinCrcError> {
+        let payload = payload.as_ref();
+        let mut buf = BytesMut::new();
+        buf.resize(Self::size_hint_with(config, payload.len())?, 0);
+        let len = Self::commit_frame_with(config, payload, &mut buf)?;
+        buf.truncate(len);
+        Ok(buf.freeze())
+    }
+
+    /// Same as `frame`, with the frame allocated from `alloc`.
+    #[cfg(feature = "allocator-api")]
+    pub fn frame_in<P: AsRef<[u8]>, A: Allocator>(payload: P, alloc: A) -> Result<AllocVec<u8, A>, BinCrcError> {
+        Self::frame_with_in(&BinCrcConfig::default(), payload, alloc)
+    }
+
+    #[cfg(feature = "allocator-api")]
+    pub fn frame_with_in<P: AsRef<[u8]>, A: Allocator>(
+        config: &BinCrcConfig,
+        payload: P,
+        alloc: A
+    ) -> Result<AllocVec<u8, A>, BinCrcError>
+    {
+        let payload = payload.as_ref();
+        let size_hint = Self::size_hint_with(config, payload.len())?;
+        let mut buf = AllocVec::with_capacity_in(size_hint, alloc);
+        buf.resize(size_hint, 0);
+        let len = Self::commit_frame_with(config, payload, &mut buf)?;
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Framed bytes of all `payloads` one after another, produced lazily, so that only one
+    /// frame is held at a time. See `EncodeIter` for payloads that can't be framed.
+    #[cfg(feature = "std")]
+    pub fn encode_iter<I: IntoIterator>(payloads: I) -> EncodeIter<N, I::IntoIter> {
+        Self::encode_iter_with(&BinCrcConfig::default(), payloads)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode_iter_with<I: IntoIterator>(config: &BinCrcConfig, payloads: I) -> EncodeIter<N, I::IntoIter> {
+        EncodeIter::new(L::config(config), payloads.into_iter())
+    }
+}
+
+const START_BYTES: [u8; 3] = [2, 3, 4];
+
+/// Part of the frame the next byte belongs to, with the number of bytes still needed to complete
+/// the frame, see `BinCrcDecoder::bytes_needed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytesNeeded {
+    /// Length field or the address in front of it
+    NeedLength(usize),
+    NeedPayload(usize),
+    /// CRC, optional length copy and stop byte, or the CRC alone with `CrcPosition::BeforePayload`
+    NeedTrailer(usize)
+}
+
+/// Outcome of a single decode attempt at the read position, ranges are buffer indices.
+/// Only public with the `test-support` feature, see `decode_step`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeResult {
+    /// At least this many more bytes are needed before the next attempt
+    NeedMoreBytes(usize),
+    InvalidData,
+    /// Header is fine, but the frame is longer than the buffer, see `DecoderEvent::OversizedFrame`
+    Oversize { claimed_len: usize },
+    /// Frame of this many bytes with the payload at the range
+    Consumed(usize, Range<usize>),
+    /// Framing is fine, but CRC is not, count and range are the same as in Consumed
+    CrcMismatch { count: usize, range: Range<usize>, expected_crc: u32, received_crc: u32 }
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>, L: LenField> Decoder for BinCrcDecoder<N, L> {
+    type Item = Vec<Vec<u8>>;
+    type Error = BinCrcError;
+
+    fn decode(&mut self, acc: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let limit = self.config.max_frames_per_decode.unwrap_or(usize::MAX);
+        // Upper bound on the number of frames, capped so that a large read of few frames or
+        // junk doesn't reserve far more than it holds
+        let max_frames = ((self.buffered() + acc.len()) / self.config.min_wire_len()).min(limit);
+        let mut frames = Vec::with_capacity(max_frames.min(MAX_PREALLOCATED_FRAMES));
+        self.decode_with(acc, &mut FrameSink(&mut |frame: &[u8]| frames.push(Vec::from(frame))), limit);
+        // Bytes eaten without completing a frame are no item yet, not an empty batch
+        if frames.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(frames))
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>, L: LenField> BinCrcDecoder<N, L> {
+    // Eats from acc until limit frames are emitted or acc is empty
+    fn decode_with<S: Sink>(&mut self, acc: &mut BytesMut, sink: &mut S, limit: usize) {
+        // Complete frames may be left buffered when the previous call hit the limit
+        let mut emitted = self.scan(sink, limit);
+        let mut consumed = 0;
+        while consumed < acc.len() && emitted < limit {
+            let bulk = self.eat_bulk(&acc[consumed..]);
+            if bulk != 0 {
+                consumed += bulk;
+                continue;
+            }
+            emitted += self.eat_byte_inner(acc[consumed], sink, limit - emitted);
+            consumed += 1;
+        }
+        acc.advance(consumed);
+    }
+}
+
+/// Frame along with where it was in the stream, see `OffsetDecoder`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamFrame {
+    pub frame: Vec<u8>,
+    /// Offset of the start byte, counted the same way as `BinCrcDecoder::position`
+    pub stream_offset: u64
+}
+
+/// `Decoder` yielding one `StreamFrame` at a time instead of batches of bare frames.
+#[cfg(feature = "std")]
+pub struct OffsetDecoder<N: ArrayLength<u8>, L: LenField = DynLen> {
+    decoder: BinCrcDecoder<N, L>
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>, L: LenField> OffsetDecoder<N, L> {
+    pub fn new(decoder: BinCrcDecoder<N, L>) -> Self {
+        OffsetDecoder { decoder }
+    }
+
+    pub fn decoder(&self) -> &BinCrcDecoder<N, L> {
+        &self.decoder
+    }
+
+    pub fn into_inner(self) -> BinCrcDecoder<N, L> {
+        self.decoder
+    }
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>, L: LenField> Decoder for OffsetDecoder<N, L> {
+    type Item = StreamFrame;
+    type Error = BinCrcError;
+
+    fn decode(&mut self, acc: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let mut frame = None;
+        self.decoder.decode_with(acc, &mut OffsetSink(&mut |payload: &[u8], offsets: Range<u64>| {
+            frame = Some(StreamFrame { frame: Vec::from(payload), stream_offset: offsets.start });
+        }), 1);
+        Ok(frame)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>, L: LenField> Encoder for BinCrcDecoder<N, L> {
+    type Item = Vec<u8>;
+    type Error = BinCrcError;
+
+    fn encode(&mut self, item: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        if !encoder::encode::<N>(&self.config, item.as_slice(), buf)? {
+            self.stats.frames_skipped += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{BinCrc, Checksum8, CrcPosition, LengthUnit, LengthWidth, Preamble, Resync};
+    use crate::typenum::{U512, U64};
+
+    fn framed(payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0; BinCrc::<U64>::size_hint(payload.len()).unwrap()];
+        BinCrc::<U64>::commit_frame(payload, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn it_works() {
+        assert_eq!(2 + 2, 4);
+    }
+
+    #[test]
+    fn suspect_frames() {
+        let mut stream = framed(&[0x10, 0x20, 0x30]);
+        stream[4] ^= 0x70; // corrupt the last payload byte
+        stream.extend_from_slice(&framed(&[4, 5]));
+
+        let mut frames = Vec::new();
+        let mut suspects = Vec::new();
+        let mut dec = BinCrc::<U64>::new();
+        for b in &stream {
+            dec.eat_byte_with_suspect(*b, &mut |f| frames.push(f.to_vec()), &mut |s| {
+                suspects.push((s.payload.to_vec(), s.expected_crc, s.received_crc));
+            });
+        }
+        assert_eq!(frames, vec![vec![4, 5]]);
+        assert_eq!(suspects.len(), 1);
+        let (payload, expected_crc, received_crc) = &suspects[0];
+        assert_eq!(payload, &vec![0x10, 0x20, 0x40]);
+        assert_eq!(*expected_crc, crc16::State::<crc16::XMODEM>::calculate(&[0x10, 0x20, 0x40]) as u32);
+        assert_eq!(*received_crc, crc16::State::<crc16::XMODEM>::calculate(&[0x10, 0x20, 0x30]) as u32);
+
+        // Default path still discards it
+        let mut frames = Vec::new();
+        let mut dec = BinCrc::<U64>::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![4, 5]]);
+    }
+
+    #[test]
+    fn indices_stay_bounded() {
+        let mut stream = Vec::new();
+        for i in 0..64u8 {
+            stream.extend_from_slice(&framed(&[i; 7][..1 + (i as usize % 7)]));
+            if i % 5 == 0 {
+                stream.extend_from_slice(&[0xAA, i, 0x55]); // junk in between some frames
+            }
+        }
+
+        let mut dec = BinCrc::<U64>::new();
+        let mut count = 0usize;
+        for _ in 0..(1_000_000 / 64) {
+            for b in &stream {
+                dec.eat_byte(*b, &mut |_| count += 1);
+                assert!(dec.read_idx <= dec.write_idx);
+                assert!(dec.write_idx < 2 * 64);
+            }
+        }
+        assert_eq!(count, 1_000_000 / 64 * 64);
+    }
+
+    #[test]
+    fn preamble() {
+        let config = BinCrcConfig { preamble: Some(Preamble { byte: 0xAA, len: 2 }), ..Default::default() };
+        let len = BinCrc::<U64>::size_hint_with(&config, 3).unwrap();
+        assert_eq!(len, BinCrc::<U64>::size_hint(3).unwrap() + 2);
+        let mut buf = vec![0; len];
+        BinCrc::<U64>::commit_frame_with(&config, &[7, 8, 9], &mut buf).unwrap();
+        assert_eq!(&buf[..3], &[0xAA, 0xAA, 2]);
+        assert!(matches!(BinCrc::<U64>::commit_frame_with(&config, &[7, 8, 9], &mut buf[1..]),
+                         Err(BinCrcError::NotEnoughSpace { .. })));
+
+        let mut frames = Vec::new();
+        let mut dec = BinCrc::<U64>::with_config(config);
+        for b in buf.iter().chain(buf.iter()) {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![7, 8, 9], vec![7, 8, 9]]);
+    }
+
+    #[test]
+    fn fill_and_drain() {
+        let mut stream = Vec::new();
+        for i in 1..=5u8 {
+            stream.extend_from_slice(&framed(&[i; 6]));
+        }
+        let partial = framed(&[9; 4]);
+        stream.extend_from_slice(&partial[..3]);
+
+        let mut dec = BinCrc::<U64>::new();
+        assert_eq!(dec.fill(&stream), stream.len());
+        let frames: Vec<Vec<u8>> = dec.drain().collect();
+        assert_eq!(frames, (1..=5u8).map(|i| vec![i; 6]).collect::<Vec<_>>());
+        assert!(dec.poll_frame().is_none());
+
+        let mut frames = Vec::new();
+        for b in &partial[3..] {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![9; 4]]);
+
+        // Only what fits in the buffer is taken
+        let big: Vec<u8> = (0..10).flat_map(|_| framed(&[1; 10])).collect();
+        assert_eq!(dec.fill(&big), 64);
+        assert_eq!(dec.drain().count(), 4);
+        let mut taken = 64;
+        let mut count = 0;
+        while taken < big.len() {
+            taken += dec.fill(&big[taken..]);
+            count += dec.drain().count();
+        }
+        assert_eq!(count, 6);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn fixed_length_width() {
+        let u16_config = BinCrcConfig { length_width: LengthWidth::U16, ..Default::default() };
+        let mut buf = vec![0; BinCrc::<U64>::size_hint_with(&u16_config, 10).unwrap()];
+        assert_eq!(buf.len(), 3 + 10 + 3);
+        BinCrc::<U64>::commit_frame_with(&u16_config, &[5; 10], &mut buf).unwrap();
+        assert_eq!(&buf[..3], &[3, 0, 10]);
+
+        let decode = |config: BinCrcConfig, stream: &[u8]| {
+            let mut frames = Vec::new();
+            let mut dec = BinCrc::<U64>::with_config(config);
+            for b in stream {
+                dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+            }
+            frames
+        };
+        assert_eq!(decode(u16_config, &buf), vec![vec![5; 10]]);
+        assert!(decode(BinCrcConfig::default(), &buf).is_empty());
+        assert!(decode(u16_config, &framed(&[5; 10])).is_empty());
+
+        let u8_config = BinCrcConfig { length_width: LengthWidth::U8, ..Default::default() };
+        assert!(matches!(BinCrc::<U64>::size_hint_with(&u8_config, 256), Err(BinCrcError::TooBig)));
+        assert_eq!(decode(u8_config, &framed(&[5; 10])), vec![vec![5; 10]]);
+    }
+
+    #[test]
+    fn raw_frames() {
+        let frame = framed(&[1, 2, 3]);
+        let mut stream = vec![0xFF, 0x00];
+        stream.extend_from_slice(&frame);
+
+        let mut seen = Vec::new();
+        let mut dec = BinCrc::<U64>::new();
+        for b in &stream {
+            dec.eat_byte_with_raw(*b, &mut |payload, raw| seen.push((payload.to_vec(), raw.to_vec())));
+        }
+        assert_eq!(seen, vec![(vec![1, 2, 3], frame)]);
+    }
+
+    #[test]
+    fn frame_len_at_buffer_size() {
+        // Length equal to N can't fit together with the header and trailer
+        let mut stream = vec![2, 64, 0xAA, 0xBB];
+        stream.extend_from_slice(&framed(&[1, 2]));
+        // Largest frame that does fit
+        stream.extend_from_slice(&framed(&[7; 64 - 5]));
+
+        let mut frames = Vec::new();
+        let mut dec = BinCrc::<U64>::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+            assert!(dec.write_idx <= 64);
+        }
+        assert_eq!(frames, vec![vec![1, 2], vec![7; 64 - 5]]);
+    }
+
+    // Start byte 0x10, 1 byte length, payload and a xor of the payload
+    struct XorFormat;
+
+    impl FrameHandler for XorFormat {
+        fn decode_frame(&self, data: &[u8]) -> HandlerResult {
+            if data.len() < 2 {
+                return HandlerResult::NeedMoreBytes(2 - data.len());
+            }
+            let len = data[1] as usize;
+            if data.len() < len + 3 {
+                return HandlerResult::NeedMoreBytes(len + 3 - data.len());
+            }
+            let xor = data[2..2 + len].iter().fold(0, |acc, b| acc ^ b);
+            if xor == data[2 + len] {
+                HandlerResult::Consumed(len + 3, 2..2 + len)
+            } else {
+                HandlerResult::InvalidData
+            }
+        }
+    }
+
+    #[test]
+    fn custom_handlers() {
+        static XOR: XorFormat = XorFormat;
+        let mut stream = framed(&[1, 2]);
+        stream.extend_from_slice(&[0x10, 3, 0xA, 0xB, 0xC, 0xA ^ 0xB ^ 0xC]);
+        stream.extend_from_slice(&[0x10, 1, 0x5, 0x6]); // bad xor
+        stream.extend_from_slice(&framed(&[3, 4]));
+
+        let mut frames = Vec::new();
+        let mut dec = BinCrc::<U64>::new();
+        dec.register_handler(0x10, &XOR).unwrap();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![1, 2], vec![0xA, 0xB, 0xC], vec![3, 4]]);
+
+        for b in 0x11..0x11 + MAX_HANDLERS as u8 - 1 {
+            dec.register_handler(b, &XOR).unwrap();
+        }
+        assert!(matches!(dec.register_handler(0x20, &XOR), Err(BinCrcError::TooManyHandlers)));
+        dec.register_handler(0x10, &XOR).unwrap();
+    }
+
+    #[test]
+    fn verify_frames() {
+        let frame = framed(&[1, 2, 3]);
+        assert_eq!(BinCrc::<U64>::verify_frame(&frame).unwrap(), 2..5);
+
+        let check = |buf: &[u8]| BinCrc::<U64>::verify_frame(buf).unwrap_err();
+        let mut bad = frame.clone();
+        bad[0] = 7;
+        assert!(matches!(check(&bad), BinCrcError::BadStart));
+        let mut bad = frame.clone();
+        bad[1] = 0;
+        assert!(matches!(check(&bad), BinCrcError::BadLength));
+        assert!(matches!(check(&frame[..frame.len() - 1]), BinCrcError::BadLength));
+        assert!(matches!(check(&[&frame[..], &[0]].concat()), BinCrcError::BadLength));
+        let mut bad = frame.clone();
+        *bad.last_mut().unwrap() = 4;
+        assert!(matches!(check(&bad), BinCrcError::BadStop));
+        let mut bad = frame.clone();
+        bad[3] ^= 1;
+        assert!(matches!(check(&bad), BinCrcError::CrcMismatch));
+    }
+
+    #[test]
+    fn oversize_recovery() {
+        // Full buffer with a good frame in the tail behind a junk header
+        let mut stream = vec![2, 50];
+        stream.resize(64 - 8, 0);
+        stream.extend_from_slice(&framed(&[1, 2, 3]));
+        assert_eq!(stream.len(), 64);
+
+        let decode = |recovery| {
+            let config = BinCrcConfig { oversize_recovery: recovery, ..Default::default() };
+            let mut dec = BinCrc::<U64>::with_config(config);
+            assert_eq!(dec.fill(&stream), 64);
+            let mut frames = Vec::new();
+            for b in &framed(&[4]) {
+                dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+            }
+            frames
+        };
+        assert_eq!(decode(OversizeRecovery::Reset), vec![vec![4]]);
+        assert_eq!(decode(OversizeRecovery::Rescan), vec![vec![1, 2, 3], vec![4]]);
+    }
+
+    #[test]
+    fn idle_bytes() {
+        let mut stream = Vec::new();
+        for i in 0..3u8 {
+            stream.extend_from_slice(&framed(&[i]));
+            stream.extend_from_slice(&[0xFF; 4]);
+        }
+        stream.extend_from_slice(&[0x55, 0x66]);
+
+        let config = BinCrcConfig { idle_byte: Some(0xFF), ..Default::default() };
+        let mut dec = BinCrc::<U64>::with_config(config);
+        for b in &stream {
+            dec.eat_byte(*b, &mut |_| ());
+        }
+        assert_eq!(*dec.stats(), DecoderStats { frames: 3, junk_bytes: 2, idle_bytes: 12, ..Default::default() });
+
+        let mut dec = BinCrc::<U64>::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |_| ());
+        }
+        assert_eq!(*dec.stats(), DecoderStats { frames: 3, junk_bytes: 14, ..Default::default() });
+        dec.reset_stats();
+        assert_eq!(*dec.stats(), DecoderStats::default());
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn trailing_length_check() {
+        let config = BinCrcConfig { trailing_length_check: true, ..Default::default() };
+        let len = BinCrc::<U64>::size_hint_with(&config, 3).unwrap();
+        assert_eq!(len, BinCrc::<U64>::size_hint(3).unwrap() + 1);
+        let mut frame = vec![0; len];
+        BinCrc::<U64>::commit_frame_with(&config, &[7, 8, 9], &mut frame).unwrap();
+        assert_eq!(&frame[frame.len() - 2..], &[3, 3]);
+        assert_eq!(BinCrc::<U64>::verify_frame_with(&config, &frame).unwrap(), 2..5);
+
+        // CRC only covers the payload, so it still passes
+        let mut bad = frame.clone();
+        bad[frame.len() - 2] = 4;
+        assert!(matches!(BinCrc::<U64>::verify_frame_with(&config, &bad), Err(BinCrcError::BadLength)));
+
+        let u16_config = BinCrcConfig { length_width: LengthWidth::U16, ..config };
+        let mut frame = vec![0; BinCrc::<U64>::size_hint_with(&u16_config, 3).unwrap()];
+        BinCrc::<U64>::commit_frame_with(&u16_config, &[7, 8, 9], &mut frame).unwrap();
+        assert_eq!(&frame[frame.len() - 3..], &[0, 3, 3]);
+        let mut frames = Vec::new();
+        let mut dec = BinCrc::<U64>::with_config(u16_config);
+        for b in &frame {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![7, 8, 9]]);
+    }
+
+    #[test]
+    fn decoder_preallocates() {
+        let mut acc = BytesMut::new();
+        for i in 0..(65536 / 6) {
+            acc.extend_from_slice(&framed(&[i as u8]));
+        }
+        let frames = BinCrc::<U64>::new().decode(&mut acc).unwrap().unwrap();
+        assert_eq!(frames.len(), 65536 / 6);
+
+        // Short burst fits the estimate exactly
+        for i in 0..10 {
+            acc.extend_from_slice(&framed(&[i]));
+        }
+        let frames = BinCrc::<U64>::new().decode(&mut acc).unwrap().unwrap();
+        assert_eq!(frames.capacity(), 10);
+
+        // Large read holding a single frame doesn't reserve room for thousands
+        acc.extend_from_slice(&[0x55; 65536]);
+        acc.extend_from_slice(&framed(&[1]));
+        let frames = BinCrc::<U64>::new().decode(&mut acc).unwrap().unwrap();
+        assert_eq!(frames.len(), 1);
+        assert!(frames.capacity() <= MAX_PREALLOCATED_FRAMES);
+    }
+
+    #[test]
+    fn decode_junk_and_partial() {
+        let mut dec = BinCrc::<U64>::new();
+        let mut acc = BytesMut::from(&[0x55; 40][..]);
+        assert_eq!(dec.decode(&mut acc).unwrap(), None);
+        assert!(acc.is_empty());
+        let frame = framed(&[1, 2, 3]);
+        acc.extend_from_slice(&frame[..4]);
+        assert_eq!(dec.decode(&mut acc).unwrap(), None);
+        assert!(acc.is_empty());
+        acc.extend_from_slice(&frame[4..]);
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(vec![vec![1, 2, 3]]));
+        assert_eq!(dec.decode(&mut acc).unwrap(), None);
+    }
+
+    #[test]
+    fn crc_anchored_resync() {
+        let a = framed(&[0x10, 0x11]);
+        let mut b = framed(&[0x20, 0x21, 0x22]);
+        b[0] = 0x22; // start byte hit by noise
+        let mut c = framed(&[0x30; 12]);
+        *c.last_mut().unwrap() = 0x13; // stop byte hit by noise
+        let d = framed(&[0x40]);
+        let stream: Vec<u8> = [&a[..], &[0xEE, 0xEF], &b, &c, &d].concat();
+
+        let decode = |config: BinCrcConfig| {
+            let mut dec = BinCrc::<U64>::with_config(config);
+            let mut frames = Vec::new();
+            for _ in 0..50 {
+                for b in &stream {
+                    dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+                }
+            }
+            (frames, *dec.stats())
+        };
+        let (frames, _) = decode(BinCrcConfig::default());
+        assert!(!frames.contains(&vec![0x20, 0x21, 0x22]));
+        assert!(!frames.contains(&vec![0x30; 12]));
+
+        let (frames, stats) = decode(BinCrcConfig { resync: Resync::CrcAnchored, ..Default::default() });
+        let expected = [vec![0x10, 0x11], vec![0x20, 0x21, 0x22], vec![0x30; 12], vec![0x40]];
+        assert_eq!(frames, expected.iter().cycle().take(200).cloned().collect::<Vec<_>>());
+        assert_eq!(stats.crc_anchored_frames, 100);
+        assert_eq!(stats.junk_bytes, 100);
+    }
+
+    #[test]
+    fn text_frames() {
+        let mut stream = Vec::new();
+        for item in &[&b"set 1"[..], &[0xC3, 0x28], "привет".as_bytes()] {
+            let mut buf = vec![0; BinCrc::<U64>::size_hint(item.len()).unwrap()];
+            match core::str::from_utf8(item) {
+                Ok(text) => BinCrc::<U64>::commit_str(text, &mut buf).unwrap(),
+                Err(_) => BinCrc::<U64>::commit_frame(item, &mut buf).unwrap()
+            };
+            stream.extend_from_slice(&buf);
+        }
+        let mut dec = BinCrc::<U64>::new();
+        let mut texts = Vec::new();
+        let mut errors = 0;
+        for b in &stream {
+            if let Err(e) = dec.decode_text(*b, &mut |text| texts.push(text.to_string())) {
+                assert!(matches!(e, BinCrcError::InvalidUtf8));
+                errors += 1;
+            }
+        }
+        assert_eq!(texts, ["set 1", "привет"]);
+        assert_eq!(errors, 1);
+    }
+
+    #[test]
+    fn max_frames_per_decode() {
+        let mut dec = BinCrc::<U64>::with_config(BinCrcConfig { max_frames_per_decode: Some(2), ..Default::default() });
+        let mut acc = BytesMut::new();
+        for i in 0..5 {
+            acc.extend_from_slice(&framed(&[i]));
+        }
+        acc.extend_from_slice(&framed(&[5])[..3]);
+        let mut frames = Vec::new();
+        let mut calls = 0;
+        while let Some(batch) = dec.decode(&mut acc).unwrap() {
+            assert!(batch.len() <= 2);
+            frames.extend(batch);
+            calls += 1;
+        }
+        assert_eq!(calls, 3);
+        assert_eq!(frames, (0..5).map(|i| vec![i]).collect::<Vec<_>>());
+        assert!(acc.is_empty());
+
+        // Partial frame left in the decoder is finished by the next call
+        acc.extend_from_slice(&framed(&[5])[3..]);
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(vec![vec![5]]));
+        assert_eq!(dec.decode(&mut acc).unwrap(), None);
+
+        // Switching from one frame per call to batching everything
+        for i in 0..3 {
+            acc.extend_from_slice(&framed(&[i]));
+        }
+        dec.set_max_frames_per_decode(Some(1));
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(vec![vec![0]]));
+        dec.set_max_frames_per_decode(None);
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(vec![vec![1], vec![2]]));
+
+        // Full buffer rescanned one frame per call, the frames behind stay buffered
+        let config = BinCrcConfig {
+            oversize_recovery: OversizeRecovery::Rescan,
+            max_frames_per_decode: Some(1),
+            ..Default::default()
+        };
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut stream = [framed(&[1; 16]), framed(&[2; 16]), framed(&[3; 16])].concat();
+        stream.push(0x55);
+        assert_eq!(dec.fill(&stream), 64);
+        let mut acc = BytesMut::from(&framed(&[4])[..]);
+        let mut frames = Vec::new();
+        while let Some(batch) = dec.decode(&mut acc).unwrap() {
+            assert_eq!(batch.len(), 1);
+            frames.extend(batch);
+        }
+        assert_eq!(frames, vec![vec![1; 16], vec![2; 16], vec![3; 16], vec![4]]);
+        assert_eq!(dec.stats().junk_bytes, 1);
+    }
+
+    #[test]
+    fn resync_events() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Monitor {
+            events: AtomicUsize,
+            skipped: AtomicUsize
+        }
+        impl SyncObserver for Monitor {
+            fn resynced(&self, skipped_bytes: usize) {
+                self.events.fetch_add(1, Ordering::Relaxed);
+                self.skipped.fetch_add(skipped_bytes, Ordering::Relaxed);
+            }
+        }
+        static MONITOR: Monitor = Monitor { events: AtomicUsize::new(0), skipped: AtomicUsize::new(0) };
+
+        let mut dec = BinCrc::<U64>::new();
+        dec.set_observer(&MONITOR);
+        let stream: Vec<u8> = [
+            &framed(&[1])[..], &[0xEE, 0xEF, 0xF0], &framed(&[2]), &framed(&[3]), &[0xEE], &framed(&[4])
+        ].concat();
+        let mut frames = 0;
+        for b in &stream {
+            dec.eat_byte(*b, &mut |_| frames += 1);
+        }
+        assert_eq!(frames, 4);
+        assert_eq!(MONITOR.events.load(Ordering::Relaxed), 2);
+        assert_eq!(MONITOR.skipped.load(Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn dropped_frames_in_decode() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Health {
+            crc_errors: AtomicUsize,
+            oversize: AtomicUsize
+        }
+        impl SyncObserver for Health {
+            fn resynced(&self, _skipped_bytes: usize) {}
+            fn frame_dropped(&self, error: BinCrcError) {
+                match error {
+                    BinCrcError::CrcMismatch => self.crc_errors.fetch_add(1, Ordering::Relaxed),
+                    BinCrcError::TooBig => self.oversize.fetch_add(1, Ordering::Relaxed),
+                    _ => panic!("unexpected {:?}", error)
+                };
+            }
+        }
+        static HEALTH: Health = Health { crc_errors: AtomicUsize::new(0), oversize: AtomicUsize::new(0) };
+
+        // Stop byte after the bad CRC would make for another oversize frame otherwise
+        let mut dec = BinCrc::<U64>::with_config(BinCrcConfig { stop_byte_not_start: true, ..Default::default() });
+        dec.set_observer(&HEALTH);
+        let mut bad = framed(&[0x11]);
+        bad[3..5].copy_from_slice(&[0xFF, 0xFF]);
+        let mut acc = BytesMut::new();
+        acc.extend_from_slice(&[&bad[..], &framed(&[3]), &[2, 100, 0xEE], &framed(&[4])].concat());
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(vec![vec![3], vec![4]]));
+        assert_eq!(HEALTH.crc_errors.load(Ordering::Relaxed), 1);
+        assert_eq!(HEALTH.oversize.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn settle_bytes() {
+        let config = BinCrcConfig { settle_bytes: 2, ..Default::default() };
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut frames = Vec::new();
+        // Turnaround noise may contain start bytes
+        let stream = [&framed(&[1])[..], &[0x02, 0x03], &framed(&[2]), &[0xEE, 0x02, 0x01], &framed(&[3])].concat();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, [vec![1], vec![2], vec![3]]);
+        assert_eq!(dec.stats().idle_bytes, 4);
+        assert_eq!(dec.stats().junk_bytes, 1);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn bytes_needed() {
+        use BytesNeeded::*;
+
+        let frame = BinCrc::<U512>::frame(vec![7; 300]).unwrap();
+        let mut dec = BinCrc::<U512>::new();
+        let mut stages = Vec::new();
+        assert_eq!(dec.bytes_needed(), None);
+        for b in &frame {
+            dec.eat_byte(*b, &mut |_| {});
+            stages.push(dec.bytes_needed());
+        }
+        assert_eq!(stages[..3], [Some(NeedLength(2)), Some(NeedLength(1)), Some(NeedPayload(303))]);
+        assert_eq!(stages[301..], [Some(NeedPayload(4)), Some(NeedTrailer(3)), Some(NeedTrailer(2)), Some(NeedTrailer(1)), None]);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn varint_length() {
+        let config = BinCrcConfig { length_width: LengthWidth::Varint, trailing_length_check: true, ..Default::default() };
+        let mut stream = Vec::new();
+        for &(len, len_field) in &[(1, &[0x01][..]), (127, &[0x7F]), (128, &[0x80, 0x01]), (300, &[0xAC, 0x02])] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let mut buf = vec![0; BinCrc::<U512>::size_hint_with(&config, len).unwrap()];
+            BinCrc::<U512>::commit_frame_with(&config, &payload, &mut buf).unwrap();
+            assert_eq!(buf[0], 2);
+            assert_eq!(&buf[1..1 + len_field.len()], len_field);
+            assert_eq!(buf.len(), 1 + len_field.len() + len + 2 + len_field.len() + 1);
+            stream.extend_from_slice(&buf);
+        }
+        // Overlong and too long length fields
+        stream.extend_from_slice(&[2, 0x81, 0x00, 0x02, 2, 0xFF, 0xFF, 0xFF, 0x01]);
+
+        let mut dec = BinCrc::<U512>::with_config(config);
+        let mut lens = Vec::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| lens.push(f.len()));
+        }
+        assert_eq!(lens, [1, 127, 128, 300]);
+
+        // Short frames are the same as with an 8 bit length
+        let mut dec = BinCrc::<U512>::with_config(BinCrcConfig { length_width: LengthWidth::Varint, ..Default::default() });
+        let mut frames = Vec::new();
+        for b in &framed(&[0x42]) {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, [[0x42]]);
+    }
+
+    #[test]
+    fn junk_gap_alarm() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Alarm(AtomicUsize);
+        impl SyncObserver for Alarm {
+            fn resynced(&self, _skipped_bytes: usize) {}
+            fn gap_exceeded(&self, junk_run: usize) {
+                assert_eq!(junk_run, 4);
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        static ALARM: Alarm = Alarm(AtomicUsize::new(0));
+
+        let mut dec = BinCrc::<U64>::with_config(BinCrcConfig { max_junk_run: Some(3), ..Default::default() });
+        dec.set_observer(&ALARM);
+        let glitch = [0xEE, 0xEE, 0xEE];
+        let burst = [0xEE; 10];
+        let stream: Vec<u8> = [
+            &framed(&[1])[..], &glitch, &framed(&[2]), &glitch, &framed(&[3]), &burst, &framed(&[4]), &burst
+        ].concat();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |_| {});
+        }
+        assert_eq!(dec.stats().frames, 4);
+        assert_eq!(ALARM.0.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn allocating_frame() {
+        assert_eq!(BinCrc::<U64>::frame(vec![1, 2, 3]).unwrap(), framed(&[1, 2, 3]));
+        assert_eq!(BinCrc::<U64>::frame("abc").unwrap(), framed(b"abc"));
+        assert!(matches!(BinCrc::<U64>::frame(vec![0; 600]), Err(BinCrcError::TooBig)));
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn drip_feed() {
+        let configs = [
+            BinCrcConfig::default(),
+            BinCrcConfig { length_width: LengthWidth::U16, ..Default::default() },
+            BinCrcConfig { trailing_length_check: true, ..Default::default() },
+        ];
+        for config in &configs {
+            for &len in &[1, 3, 255, 256, 300] {
+                let payload: Vec<u8> = (0..len).map(|i| (i % 200) as u8).collect();
+                let frame = BinCrc::<U512>::frame_with(config, &payload).unwrap();
+                let header_len = config.header_len(len);
+                let mut dec = BinCrc::<U512>::with_config(*config);
+                let mut frames = Vec::new();
+                for (i, b) in frame.iter().enumerate() {
+                    dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+                    let received = i + 1;
+                    // Rest of the header, then rest of the frame, then the next start byte
+                    let expected = if received < header_len {
+                        header_len - received
+                    } else if received < frame.len() {
+                        frame.len() - received
+                    } else {
+                        1
+                    };
+                    assert_eq!(dec.bytes_left, expected, "len {} after {} bytes", len, received);
+                    assert_eq!(frames.is_empty(), received < frame.len());
+                }
+                assert_eq!(frames, [payload]);
+            }
+        }
+    }
+
+    #[test]
+    fn crc_backend() {
+        use crate::{CrcBackend, Sum16};
+
+        // Plain sum instead of a CRC, so that frames only decode with a matching backend
+        let config = BinCrcConfig { crc_backend: CrcBackend(&Sum16), ..Default::default() };
+        let frame = BinCrc::<U64>::frame_with(&config, [0x10, 0x20]).unwrap();
+        assert_eq!(frame, [2, 2, 0x10, 0x20, 0x00, 0x30, 3]);
+        assert!(BinCrc::<U64>::verify_frame_with(&config, &frame).is_ok());
+        assert!(matches!(BinCrc::<U64>::verify_frame(&frame), Err(BinCrcError::CrcMismatch)));
+        assert_ne!(config, BinCrcConfig::default());
+    }
+
+    #[test]
+    fn crc_table_frames() {
+        use crate::TableCrc;
+
+        // CRC-16/UMTS, polynomial 0x8005
+        static UMTS: TableCrc = TableCrc::from_poly(0x8005, 0, 0);
+        let config = BinCrcConfig::default().with_crc_table(&UMTS);
+        let frame = BinCrc::<U64>::frame_with(&config, *b"123456789").unwrap();
+        assert_eq!(frame[frame.len() - 3..], [0xFE, 0xE8, 3]);
+        let mut frames = Vec::new();
+        let mut dec = BinCrc::<U64>::with_config(config);
+        dec.eat_bytes(&frame, &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![b"123456789".to_vec()]);
+        assert!(matches!(BinCrc::<U64>::verify_frame(&frame), Err(BinCrcError::CrcMismatch)));
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn sum16_frames() {
+        use crate::CrcBackend;
+
+        let config = BinCrcConfig { crc_backend: CrcBackend::SUM16, ..Default::default() };
+        let frame = BinCrc::<U512>::frame_with(&config, [0xFF; 300]).unwrap();
+        assert_eq!(frame[frame.len() - 3..], [0x2A, 0xD4, 3]);
+        let mut frames = Vec::new();
+        let mut dec = BinCrc::<U512>::with_config(config);
+        dec.eat_bytes(&frame, &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![vec![0xFF; 300]]);
+        assert!(matches!(BinCrc::<U512>::verify_frame(&frame), Err(BinCrcError::CrcMismatch)));
+        let mut bad = frame.clone();
+        bad[10] = 0xFE;
+        assert!(matches!(BinCrc::<U512>::verify_frame_with(&config, &bad), Err(BinCrcError::CrcMismatch)));
+    }
+
+    #[test]
+    fn has_frame() {
+        let mut dec = BinCrc::<U64>::new();
+        assert!(!dec.has_frame());
+        let stream: Vec<u8> = [&[0xEE, 0x02, 0x00][..], &framed(&[1, 2]), &framed(&[3])].concat();
+        dec.fill(&stream[..9]);
+        assert!(!dec.has_frame());
+        dec.fill(&stream[9..stream.len() - 1]);
+        assert!(dec.has_frame());
+        dec.fill(&stream[stream.len() - 1..]);
+        let mut frames = Vec::new();
+        while dec.has_frame() {
+            frames.push(dec.poll_frame().unwrap());
+        }
+        assert_eq!(frames, [vec![1, 2], vec![3]]);
+        assert!(dec.poll_frame().is_none());
+
+        // An echo and a frame within the settle bytes after it are skipped by poll_frame
+        let config = BinCrcConfig { settle_bytes: 7, ..Default::default() };
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let sent = framed(&[7, 7]);
+        dec.expect_echo(&sent);
+        dec.fill(&sent);
+        assert!(!dec.has_frame());
+        dec.fill(&framed(&[1, 2]));
+        assert!(!dec.has_frame());
+        dec.fill(&framed(&[3]));
+        let mut frames = Vec::new();
+        while dec.has_frame() {
+            frames.push(dec.poll_frame().unwrap());
+        }
+        assert_eq!(frames, [vec![3]]);
+        assert!(dec.poll_frame().is_none());
+    }
+
+    #[cfg(feature = "len8-only")]
+    #[test]
+    fn len8_only() {
+        assert!(matches!(BinCrc::<U512>::frame(vec![0; 256]), Err(BinCrcError::InvalidLength)));
+        assert!(matches!(BinCrc::<U512>::size_hint(256), Err(BinCrcError::InvalidLength)));
+        let mut buf = [0; 300];
+        assert!(matches!(BinCrc::<U512>::commit_frame(&[0; 256], &mut buf), Err(BinCrcError::InvalidLength)));
+        let config = BinCrcConfig { length_width: LengthWidth::U16, ..Default::default() };
+        assert!(matches!(BinCrc::<U512>::frame_with(&config, [1]), Err(BinCrcError::InvalidLength)));
+        // 16 bit frame as the full build would encode it
+        let mut frame = vec![3, 0x01, 0x00];
+        frame.extend_from_slice(&[0; 256]);
+        frame.extend_from_slice(&crc16::State::<crc16::XMODEM>::calculate(&[0; 256]).to_be_bytes());
+        frame.push(3);
+        assert!(matches!(BinCrc::<U512>::verify_frame(&frame), Err(BinCrcError::BadLength)));
+        assert_eq!(BinCrc::<U512>::frame(vec![7; 255]).unwrap().len(), 260);
+    }
+
+    #[test]
+    fn read_frame_into() {
+        let mut dec = BinCrc::<U64>::new();
+        let stream: Vec<u8> = [&framed(&[1, 2, 3])[..], &framed(&[4; 10]), &framed(&[5])].concat();
+        let mut out = [0; 4];
+        let mut results = Vec::new();
+        for b in &stream {
+            match dec.read_frame_into(*b, &mut out) {
+                Some(Ok(len)) => results.push(Ok(out[..len].to_vec())),
+                Some(Err(e)) => results.push(Err(e)),
+                None => {}
+            }
+        }
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &[1, 2, 3]);
+        assert!(matches!(results[1], Err(BinCrcError::NotEnoughSpace { .. })));
+        assert_eq!(results[2].as_ref().unwrap(), &[5]);
+    }
+
+    #[test]
+    fn read_frame_into_keeps_rescued_frames() {
+        let read_all = |dec: &mut BinCrc<U64>, bytes: &[u8]| {
+            let mut out = [0; 64];
+            let mut frames = Vec::new();
+            for b in bytes {
+                if let Some(len) = dec.read_frame_into(*b, &mut out) {
+                    frames.push(out[..len.unwrap()].to_vec());
+                }
+            }
+            frames
+        };
+
+        // Full buffer rescanned with OversizeRecovery::Rescan
+        let config = BinCrcConfig { oversize_recovery: OversizeRecovery::Rescan, ..Default::default() };
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut stream = [framed(&[1; 16]), framed(&[2; 16]), framed(&[3; 16])].concat();
+        stream.push(0x55);
+        assert_eq!(dec.fill(&stream), 64);
+        assert_eq!(read_all(&mut dec, &[0x55; 4]), vec![vec![1; 16], vec![2; 16], vec![3; 16]]);
+
+        // Bogus header given up on by max_partial_bytes, with two frames behind it
+        let config = BinCrcConfig { max_partial_bytes: Some(20), ..Default::default() };
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let stream = [&[2, 40][..], &framed(&[1; 3]), &framed(&[2; 3]), &[0x55; 6]].concat();
+        assert_eq!(read_all(&mut dec, &stream), vec![vec![1; 3], vec![2; 3]]);
+    }
+
+    #[test]
+    fn crc_includes_stop() {
+        let config = BinCrcConfig { crc_includes_stop: true, ..Default::default() };
+        let frame = BinCrc::<U64>::frame_with(&config, [1, 2, 3]).unwrap();
+        let crc = crc16::State::<crc16::XMODEM>::calculate(&[1, 2, 3, 3]);
+        assert_eq!(frame[5..7], crc.to_be_bytes());
+        assert!(BinCrc::<U64>::verify_frame_with(&config, &frame).is_ok());
+        assert!(matches!(BinCrc::<U64>::verify_frame(&frame), Err(BinCrcError::CrcMismatch)));
+        assert!(matches!(BinCrc::<U64>::verify_frame_with(&config, &framed(&[1, 2, 3])), Err(BinCrcError::CrcMismatch)));
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn type_driven_length() {
+        use crate::{U16Len, U8Len};
+
+        let frame = BinCrcDecoder::<U512, U16Len>::frame([1, 2]).unwrap();
+        assert_eq!(frame[..3], [3, 0, 2]);
+        assert!(matches!(BinCrcDecoder::<U512, U8Len>::frame(vec![0; 300]), Err(BinCrcError::TooBig)));
+        // Fixed type wins over the config
+        let config = BinCrcConfig { length_width: LengthWidth::U8, ..Default::default() };
+        assert_eq!(BinCrcDecoder::<U512, U16Len>::with_config(config).config().length_width, LengthWidth::U16);
+
+        let mut dec = BinCrcDecoder::<U512, U16Len>::new();
+        let mut frames = Vec::new();
+        for b in framed(&[9]).iter().chain(&frame) {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, [[1, 2]]);
+    }
+
+    #[test]
+    fn crc_error_skips_frame() {
+        // Corrupted frame carries what looks like a complete frame in its payload
+        let inner = framed(&[0x55]);
+        let mut outer = framed(&inner);
+        outer[2 + inner.len()] ^= 0xFF;
+        let stream: Vec<u8> = [&outer[..], &framed(&[0x66])].concat();
+
+        let decode = |config: BinCrcConfig| {
+            let mut dec = BinCrc::<U64>::with_config(config);
+            let mut frames = Vec::new();
+            for b in &stream {
+                dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+            }
+            (frames, dec.stats().junk_bytes)
+        };
+        assert_eq!(decode(BinCrcConfig::default()).0, [vec![0x55], vec![0x66]]);
+        let (frames, junk) = decode(BinCrcConfig { crc_error_skips_frame: true, ..Default::default() });
+        assert_eq!(frames, [vec![0x66]]);
+        assert_eq!(junk, outer.len() as u64);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn eat_bytes_same_as_eat_byte() {
+        let mut stream = Vec::new();
+        for i in 0..200u32 {
+            stream.extend_from_slice(&BinCrc::<U512>::frame(vec![i as u8; (i as usize * 7) % 300 + 1]).unwrap());
+            if i % 9 == 0 {
+                stream.extend_from_slice(&[0x02, 0x01, 0xEE]);
+            }
+        }
+        let mut expected = Vec::new();
+        let mut dec = BinCrc::<U512>::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| expected.push(f.to_vec()));
+        }
+        for chunk_len in &[1, 7, 64, 1000, stream.len()] {
+            let mut frames = Vec::new();
+            let mut dec = BinCrc::<U512>::new();
+            for chunk in stream.chunks(*chunk_len) {
+                dec.eat_bytes(chunk, &mut |f| frames.push(f.to_vec()));
+            }
+            assert_eq!(frames, expected);
+        }
+        assert_eq!(expected.len(), 200);
+    }
+
+    #[test]
+    fn echo_suppression() {
+        let mut dec = BinCrc::<U64>::new();
+        let sent = framed(&[1, 2, 3]);
+        let mut frames = Vec::new();
+
+        dec.expect_echo(&sent);
+        for b in sent.iter().chain(&framed(&[4])) {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, [vec![4]]);
+
+        // Something else came first, the echo expectation is gone
+        dec.expect_echo(&sent);
+        for b in framed(&[5]).iter().chain(&sent) {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, [vec![4], vec![5], vec![1, 2, 3]]);
+        assert_eq!(dec.stats().echoes_suppressed, 1);
+        assert_eq!(dec.stats().frames, 3);
+    }
+
+    #[test]
+    #[cfg(not(feature = "len8-only"))]
+    fn size_hint_const() {
+        use crate::{U16Len, U8Len};
+
+        const TX_SIZE: usize = BinCrc::<U512>::size_hint_const(300);
+        assert_eq!(TX_SIZE, 306);
+        for len in 0..=512 {
+            assert_eq!(BinCrc::<U512>::size_hint_const(len), BinCrc::<U512>::size_hint(len).unwrap());
+            assert_eq!(BinCrc::<U512, U16Len>::size_hint_const(len), BinCrc::<U512, U16Len>::size_hint(len).unwrap());
+        }
+        for len in 0..=255 {
+            assert_eq!(BinCrc::<U64, U8Len>::size_hint_const(len), BinCrc::<U64, U8Len>::size_hint(len).unwrap());
+        }
+        assert!(std::panic::catch_unwind(|| BinCrc::<U512>::size_hint_const(513)).is_err());
+        assert!(std::panic::catch_unwind(|| BinCrc::<U64>::size_hint_const(256)).is_err());
+    }
+
+    #[test]
+    fn size_hint_matches_commit() {
+        let configs = [
+            BinCrcConfig::default(),
+            BinCrcConfig { length_width: LengthWidth::U16, ..Default::default() },
+            BinCrcConfig { length_width: LengthWidth::U8, ..Default::default() },
+            BinCrcConfig { length_width: LengthWidth::Varint, trailing_length_check: true, ..Default::default() },
+            BinCrcConfig {
+                preamble: Some(Preamble { byte: 0x55, len: 3 }),
+                checksum: Some(Checksum8::Sum),
+                trailing_length_check: true,
+                ..Default::default()
+            },
+            BinCrcConfig { length_unit: LengthUnit::Words16, ..Default::default() },
+            BinCrcConfig { length_width: LengthWidth::FixedPayload(300), trailing_length_check: true, ..Default::default() },
+            BinCrcConfig { omit_stop_byte: true, crc_position: CrcPosition::BeforePayload, ..Default::default() },
+        ];
+        let mut buf = [0xAA; 600];
+        for config in &configs {
+            for len in 0..=520 {
+                let payload = vec![0x42; len];
+                let written = BinCrc::<U512>::commit_frame_with(config, &payload, &mut buf);
+                match BinCrc::<U512>::size_hint_with(config, len) {
+                    Ok(size) => assert_eq!(written.unwrap(), size, "{:?} len {}", config, len),
+                    Err(_) => assert!(written.is_err(), "{:?} len {}", config, len)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decode_steps() {
+        let mut dec = BinCrc::<U64>::new();
+        dec.set_buffer(&[2]);
+        assert_eq!(dec.decode_step(), DecodeResult::NeedMoreBytes(1));
+        dec.set_buffer(&[2, 3, 0x10]);
+        assert_eq!(dec.decode_step(), DecodeResult::NeedMoreBytes(5));
+        dec.set_buffer(&[2, 0]);
+        assert_eq!(dec.decode_step(), DecodeResult::InvalidData);
+        dec.set_buffer(&[2, 100]);
+        assert_eq!(dec.decode_step(), DecodeResult::Oversize { claimed_len: 100 });
+
+        let mut frame = framed(&[0x10, 0x20]);
+        dec.set_buffer(&frame);
+        assert_eq!(dec.decode_step(), DecodeResult::Consumed(7, 2..4));
+        frame[2] ^= 1;
+        dec.set_buffer(&frame);
+        assert!(matches!(dec.decode_step(), DecodeResult::CrcMismatch { count: 7, .. }));
+        assert_eq!(dec.pending(), &frame[..]);
+
+        // Step doesn't consume, eating a byte does, the second byte is a false start
+        dec.eat_byte(0xEE, &mut |_| {});
+        assert_eq!(dec.pending(), &[&frame[1..], &[0xEE]].concat()[..]);
+        assert_eq!(dec.stats().junk_bytes, 1);
+    }
+
+    #[test]
+    fn delimited_framing() {
+        let config = BinCrcConfig { framing: Framing::Delimited, ..Default::default() };
+        let payloads = [vec![0x41, 0x42], vec![2, 3, 0x10, 0x30], vec![3; 40], (0..=255).collect::<Vec<u8>>()];
+        let mut stream = vec![0x03, 0xEE];
+        for payload in &payloads {
+            let frame = BinCrc::<U512>::frame_with(&config, payload).unwrap();
+            assert_eq!(frame.iter().filter(|b| **b == 3).count(), 1);
+            assert!(BinCrc::<U512>::verify_frame_with(&config, &frame).is_ok());
+            stream.extend_from_slice(&frame);
+        }
+        // CRC failure followed by a good frame
+        let mut bad = BinCrc::<U512>::frame_with(&config, [0x55, 0x66]).unwrap();
+        bad[1] ^= 0x01;
+        stream.extend_from_slice(&bad);
+        stream.extend_from_slice(&BinCrc::<U512>::frame_with(&config, [0x77]).unwrap());
+
+        let mut dec = BinCrc::<U512>::with_config(config);
+        let mut frames = Vec::new();
+        dec.eat_bytes(&stream, &mut |f| frames.push(f.to_vec()));
+        let mut expected = payloads.to_vec();
+        expected.push(vec![0x77]);
+        assert_eq!(frames, expected);
+        assert_eq!(dec.stats().crc_errors, 1);
+
+        // In place framing needs room for the worst case
+        let mut buf = [0; 16];
+        buf[4..7].copy_from_slice(&[2, 2, 2]);
+        let len = BinCrc::<U512>::commit_in_place_with(&config, &mut buf, 4..7).unwrap();
+        assert_eq!(buf[..len], BinCrc::<U512>::frame_with(&config, [2, 2, 2]).unwrap()[..]);
+    }
+
+    #[test]
+    fn oversize_frames() {
+        let mut dec = BinCrc::<U64>::new();
+        let big = BinCrc::<U512>::frame(vec![0x55; 100]).unwrap();
+        let mut frames = Vec::new();
+        dec.eat_bytes(&big, &mut |f| frames.push(f.to_vec()));
+        assert_eq!(dec.stats().oversize_frames, 1);
+        dec.eat_bytes(&framed(&[1, 2]), &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, [vec![1, 2]]);
+        assert_eq!(dec.stats().crc_errors, 0);
+        assert!(matches!(BinCrc::<U64>::verify_frame(&big), Err(BinCrcError::TooBig)));
+    }
+
+    #[test]
+    fn length_in_words() {
+        let config = BinCrcConfig { length_unit: LengthUnit::Words16, ..Default::default() };
+        let frame = BinCrc::<U512>::frame_with(&config, [1, 2, 3, 4]).unwrap();
+        assert_eq!(frame[..2], [2, 2]);
+        // 8 bit length field holds up to 510 bytes
+        let long = BinCrc::<U512>::frame_with(&config, vec![0x55; 500]).unwrap();
+        assert_eq!(long[..2], [2, 250]);
+        assert!(matches!(BinCrc::<U512>::frame_with(&config, [1, 2, 3]), Err(BinCrcError::OddLength)));
+        assert!(matches!(BinCrc::<U512>::size_hint_with(&config, 3), Err(BinCrcError::OddLength)));
+
+        let mut dec = BinCrc::<U512>::with_config(config);
+        let mut frames = Vec::new();
+        dec.eat_bytes(&[&frame[..], &long[..]].concat(), &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, [vec![1, 2, 3, 4], vec![0x55; 500]]);
+        // Same bytes read as a byte count are a different frame
+        assert!(BinCrc::<U512>::verify_frame(&frame).is_err());
+    }
+
+    #[test]
+    fn secondary_checksum() {
+        for &checksum in &[Checksum8::Xor, Checksum8::Sum] {
+            let config = BinCrcConfig { checksum: Some(checksum), trailing_length_check: true, ..Default::default() };
+            let frame = BinCrc::<U64>::frame_with(&config, [0x81, 0x82, 0x05]).unwrap();
+            assert_eq!(frame.len(), BinCrc::<U64>::size_hint(3).unwrap() + 2);
+            let expected = if checksum == Checksum8::Xor { 0x06 } else { 0x08 };
+            assert_eq!(frame[7], expected);
+            assert_eq!(BinCrc::<U64>::verify_frame_with(&config, &frame).unwrap(), 2..5);
+
+            let mut bad = frame.clone();
+            bad[7] ^= 0x40;
+            assert!(matches!(BinCrc::<U64>::verify_frame_with(&config, &bad), Err(BinCrcError::ChecksumMismatch)));
+            let mut dec = BinCrc::<U64>::with_config(config);
+            let mut frames = Vec::new();
+            dec.eat_bytes(&[&bad[..], &frame[..]].concat(), &mut |f| frames.push(f.to_vec()));
+            assert_eq!(frames, [vec![0x81, 0x82, 0x05]]);
+        }
+    }
+
+    #[test]
+    fn stream_offsets() {
+        let mut stream = vec![0xEE; 3];
+        stream.extend_from_slice(&framed(&[1, 2, 3]));
+        stream.extend_from_slice(&[0xEE, 0x03]);
+        stream.extend_from_slice(&framed(&[4]));
+        let mut dec = BinCrc::<U64>::new();
+        let mut frames = Vec::new();
+        for b in &stream {
+            dec.eat_byte_with_offsets(*b, &mut |f, offsets| frames.push((f.to_vec(), offsets)));
+        }
+        assert_eq!(frames, [(vec![1, 2, 3], 3..11), (vec![4], 13..19)]);
+        assert_eq!(dec.position(), stream.len() as u64);
+        dec.reset();
+        assert_eq!(dec.position(), 0);
+        for b in &framed(&[5]) {
+            dec.eat_byte_with_offsets(*b, &mut |f, offsets| frames.push((f.to_vec(), offsets)));
+        }
+        assert_eq!(frames[2], (vec![5], 0..6));
+    }
+
+    #[test]
+    fn check_crc() {
+        let mut frame = framed(&[1, 2, 3]);
+        assert!(BinCrc::<U64>::check_crc(&frame).unwrap());
+        frame[3] ^= 0x80;
+        assert!(!BinCrc::<U64>::check_crc(&frame).unwrap());
+        let last = frame.len() - 1;
+        frame[last] = 0;
+        assert!(matches!(BinCrc::<U64>::check_crc(&frame), Err(BinCrcError::BadStop)));
+        assert!(matches!(BinCrc::<U64>::check_crc(&frame[..4]), Err(BinCrcError::BadLength)));
+    }
+
+    #[test]
+    fn control_frames() {
+        const XON: u8 = 0x11;
+        const XOFF: u8 = 0x13;
+        let mut dec = BinCrc::<U64>::new();
+        dec.set_control_filter(|payload| payload == [XON] || payload == [XOFF]);
+        let mut events = Vec::new();
+        let stream = [framed(&[1, 2]), framed(&[XOFF]), framed(&[XON, 0]), framed(&[XON])].concat();
+        for b in &stream {
+            let mut frames = Vec::new();
+            let mut controls = Vec::new();
+            dec.eat_byte_with_control(*b, &mut |f| frames.push(f.to_vec()), &mut |c| controls.push(c[0]));
+            events.extend(frames.into_iter().map(Ok));
+            events.extend(controls.into_iter().map(Err));
+        }
+        assert_eq!(events, [Ok(vec![1, 2]), Err(XOFF), Ok(vec![XON, 0]), Err(XON)]);
+    }
+
+    #[test]
+    fn inserted_bytes() {
+        let config = BinCrcConfig { resync: Resync::CrcAnchored, ..Default::default() };
+        let frame = framed(&[1, 2, 3, 4, 5]);
+        let mut stream = Vec::new();
+        // Duplicates around the payload look like a frame with a damaged start or stop byte,
+        // regular CRC-anchored resync takes care of those
+        for &dup in &[2, 4, 6] {
+            stream.extend_from_slice(&frame[..=dup]);
+            stream.extend_from_slice(&frame[dup..]);
+        }
+        stream.extend_from_slice(&framed(&[6]));
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut frames = Vec::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, [vec![1, 2, 3, 4, 5], vec![1, 2, 3, 4, 5], vec![1, 2, 3, 4, 5], vec![6]]);
+        assert_eq!(dec.stats().insertions_skipped, 3);
+        assert_eq!(dec.stats().crc_anchored_frames, 3);
+    }
+
+    #[test]
+    #[cfg(not(feature = "len8-only"))]
+    fn max_payload() {
+        use crate::typenum::{Sum, U4, U65536, U8};
+
+        // Largest 16 bit payload plus 6 bytes of framing
+        type N = Sum<U65536, U8>;
+        let payload: Vec<u8> = (0..u16::MAX as usize).map(|i| (i % 251) as u8).collect();
+        let mut frame = vec![0; payload.len() + 6];
+        BinCrc::<N>::commit_frame(&payload, &mut frame).unwrap();
+        assert_eq!(frame[..3], [3, 0xFF, 0xFF]);
+        assert_eq!(BinCrc::<N>::verify_frame(&frame).unwrap(), 3..3 + payload.len());
+        let mut dec = Box::new(BinCrc::<N>::new());
+        let mut frames = Vec::new();
+        dec.eat_bytes(&frame, &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, [payload]);
+
+        // Frame is 6 bytes longer than the payload, 4 don't do
+        let mut dec = Box::new(BinCrc::<Sum<U65536, U4>>::new());
+        dec.eat_bytes(&frame, &mut |_| panic!("doesn't fit"));
+        assert_eq!(dec.stats().oversize_frames, 1);
+    }
+
+    #[test]
+    #[cfg(not(feature = "len8-only"))]
+    fn length_24bit() {
+        use crate::typenum::{Sum, U16, U65536};
+
+        type N = Sum<U65536, U16>;
+        let mut dec = Box::new(BinCrc::<N>::new());
+        for &(len, header) in &[(65535, [3, 0xFF, 0xFF, 0]), (65536, [4, 1, 0, 0]), (65537, [4, 1, 0, 1])] {
+            let payload: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let frame = BinCrc::<N>::frame(&payload).unwrap();
+            let header_len = header[0] as usize;
+            assert_eq!(frame.len(), header_len + len + 3);
+            assert_eq!(frame[..header_len], header[..header_len]);
+            assert_eq!(BinCrc::<N>::size_hint_const(len), frame.len());
+            let mut frames = Vec::new();
+            dec.eat_bytes(&frame, &mut |f| frames.push(f.to_vec()));
+            assert_eq!(frames, [payload]);
+        }
+        assert_eq!(dec.stats().junk_bytes, 0);
+
+        // 24 bit length within the 16 bit range is rejected, same as 16 bit within the 8 bit one
+        let payload = vec![0x42; 65535];
+        let mut frame = vec![4, 0, 0xFF, 0xFF];
+        frame.extend_from_slice(&payload);
+        frame.extend_from_slice(&crc16::State::<crc16::XMODEM>::calculate(&payload).to_be_bytes());
+        frame.push(3);
+        assert!(matches!(BinCrc::<N>::verify_frame(&frame), Err(BinCrcError::BadLength)));
+        dec.set_buffer(&frame[..4]);
+        assert_eq!(dec.decode_step(), DecodeResult::InvalidData);
+        dec.set_buffer(&[4, 1, 0, 0]);
+        assert_eq!(dec.decode_step(), DecodeResult::NeedMoreBytes(65536 + 3));
+
+        // Fixed 16 bit width has no 24 bit form
+        let config = BinCrcConfig { length_width: LengthWidth::U16, ..Default::default() };
+        assert!(matches!(BinCrc::<N>::size_hint_with(&config, 65536), Err(BinCrcError::TooBig)));
+        let mut buf = vec![0; 65536 + 10];
+        assert!(BinCrc::<N>::commit_frame_with(&config, &payload, &mut buf).is_ok());
+        assert!(matches!(BinCrc::<N>::commit_frame_with(&config, &[0; 65536], &mut buf), Err(BinCrcError::InvalidLength)));
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn length_16bit_boundary() {
+        // 255 fits into an 8 bit length, so it has no 16 bit form unless the width is fixed
+        let payload = vec![0x42; 255];
+        let mut frame = vec![3, 0, 0xFF];
+        frame.extend_from_slice(&payload);
+        frame.extend_from_slice(&crc16::State::<crc16::XMODEM>::calculate(&payload).to_be_bytes());
+        frame.push(3);
+        assert!(matches!(BinCrc::<U512>::verify_frame(&frame), Err(BinCrcError::BadLength)));
+        let config = BinCrcConfig { length_width: LengthWidth::U16, ..Default::default() };
+        assert!(BinCrc::<U512>::verify_frame_with(&config, &frame).is_ok());
+        BinCrc::<U512>::new().eat_bytes(&frame, &mut |_| panic!());
+        let default = BinCrcConfig::default();
+        assert!(matches!(frame::parse_anchored(&default, &frame, 3), frame::Anchored::NoMatch));
+        assert!(matches!(frame::parse_anchored(&config, &frame, 3), frame::Anchored::Frame(_)));
+
+        let frame = BinCrc::<U512>::frame(vec![0x42; 256]).unwrap();
+        assert_eq!(frame[..3], [3, 1, 0]);
+        assert!(BinCrc::<U512>::verify_frame(&frame).is_ok());
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let frame = framed(&[1, 2, 3, 4]);
+        let mut dec = BinCrc::<U64>::new();
+        let mut frames = Vec::new();
+        dec.eat_bytes(&frame[..5], &mut |f| frames.push(f.to_vec()));
+
+        // Nothing that doesn't feed bytes changes the pending frame
+        let mut empty = BytesMut::new();
+        for _ in 0..10 {
+            assert!(!dec.has_frame());
+            assert_eq!(dec.decode(&mut empty).unwrap(), None);
+            assert_eq!(dec.poll_frame(), None);
+        }
+        assert_eq!(dec.buffered(), 5);
+        dec.eat_bytes(&frame[5..], &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, [vec![1, 2, 3, 4]]);
+        assert_eq!(dec.stats().junk_bytes, 0);
+    }
+
+    #[test]
+    fn offset_decoder() {
+        let mut dec = OffsetDecoder::new(BinCrc::<U64>::new());
+        let mut acc = BytesMut::new();
+        acc.extend_from_slice(&[0xEE, 0xEE]);
+        acc.extend_from_slice(&framed(&[1, 2]));
+        acc.extend_from_slice(&framed(&[3])[..4]);
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(StreamFrame { frame: vec![1, 2], stream_offset: 2 }));
+        assert_eq!(dec.decode(&mut acc).unwrap(), None);
+        acc.extend_from_slice(&framed(&[3])[4..]);
+        acc.extend_from_slice(&framed(&[4]));
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(StreamFrame { frame: vec![3], stream_offset: 9 }));
+        assert_eq!(dec.decode(&mut acc).unwrap(), Some(StreamFrame { frame: vec![4], stream_offset: 15 }));
+        assert_eq!(dec.decode(&mut acc).unwrap(), None);
+        assert_eq!(dec.decoder().position(), 21);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn stop_byte_not_start() {
+        use crate::typenum::U1024;
+
+        // Payloads ending with 3, every other frame failing CRC and followed by a frame whose
+        // start byte and length make a plausible 16 bit length after the stop byte
+        let mut stream = Vec::new();
+        for i in 0..20u8 {
+            let mut bad = framed(&[0x40 + i, 0x60, 3]);
+            bad[5..7].copy_from_slice(&[0xFF, 0xFF]);
+            stream.extend_from_slice(&bad);
+            stream.extend_from_slice(&framed(&[0x50 + i, 0x60, 3]));
+        }
+        // Frames decoded as soon as their stop byte arrives
+        let on_time = |config: BinCrcConfig| {
+            let mut dec = BinCrc::<U1024>::with_config(config);
+            let mut count = 0;
+            for (i, b) in stream.iter().enumerate() {
+                dec.eat_byte_with_offsets(*b, &mut |_, offsets| {
+                    if offsets.end == i as u64 + 1 {
+                        count += 1;
+                    }
+                });
+            }
+            count
+        };
+        assert_eq!(on_time(BinCrcConfig::default()), 0);
+        assert_eq!(on_time(BinCrcConfig { stop_byte_not_start: true, ..Default::default() }), 20);
+    }
+
+    #[test]
+    #[cfg(not(feature = "len8-only"))]
+    fn address_bytes() {
+        for &length_width in &[LengthWidth::Auto, LengthWidth::U16, LengthWidth::Varint] {
+            let config = BinCrcConfig { address_len: 2, length_width, trailing_length_check: true, ..Default::default() };
+            let mut stream = vec![0x42, 2];
+            let mut buf = [0; 400];
+            for (i, &len) in [1, 10, 300].iter().enumerate() {
+                let written = BinCrc::<U512>::commit_frame_addressed_with(&config, &[0xA0, i as u8], &vec![i as u8; len], &mut buf).unwrap();
+                assert_eq!(written, BinCrc::<U512>::size_hint_with(&config, len).unwrap());
+                assert_eq!(&buf[..3], &[buf[0], 0xA0, i as u8]);
+                assert_eq!(BinCrc::<U512>::verify_frame_with(&config, &buf[..written]).unwrap().len(), len);
+                stream.extend_from_slice(&buf[..written]);
+            }
+            // Address is not covered by the CRC
+            let last = stream.len();
+            BinCrc::<U512>::commit_frame_addressed_with(&config, &[0xA0, 7], &[7], &mut buf).unwrap();
+            stream.extend_from_slice(&buf[..BinCrc::<U512>::size_hint_with(&config, 1).unwrap()]);
+            stream[last + 2] = 8;
+
+            for &resync in &[Resync::StartByte, Resync::CrcAnchored] {
+                let mut dec = BinCrc::<U512>::with_config(BinCrcConfig { resync, ..config });
+                let mut frames = Vec::new();
+                for b in &stream {
+                    dec.eat_byte_with_address(*b, &mut |address, payload| frames.push((address.to_vec(), payload.len())));
+                }
+                assert_eq!(frames, vec![(vec![0xA0, 0], 1), (vec![0xA0, 1], 10), (vec![0xA0, 2], 300), (vec![0xA0, 8], 1)]);
+            }
+            assert!(matches!(BinCrc::<U512>::commit_frame_with(&config, &[1], &mut buf), Err(BinCrcError::InvalidLength)));
+            assert!(matches!(
+                BinCrc::<U512>::commit_frame_addressed_with(&config, &[1], &[1], &mut buf),
+                Err(BinCrcError::InvalidLength)
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "len8-only"))]
+    fn max_partial_bytes() {
+        // Claims 300 bytes, but the link stalls after 100 of them
+        let mut stalled = BinCrc::<U512>::frame([0x55; 300]).unwrap();
+        stalled.truncate(100);
+        let good = framed(&[1, 2, 3]);
+        let stream = [&stalled[..], &good[..]].concat();
+
+        let config = BinCrcConfig { max_partial_bytes: Some(64), ..Default::default() };
+        let mut dec = BinCrc::<U512>::with_config(config);
+        let mut frames = Vec::new();
+        dec.eat_bytes(&stream, &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![vec![1, 2, 3]]);
+        assert_eq!(dec.stats().partial_frames_abandoned, 1);
+        assert_eq!(dec.stats().junk_bytes, 100);
+
+        // Same byte by byte, and frames up to the budget are fine
+        let mut dec = BinCrc::<U512>::with_config(BinCrcConfig { max_partial_bytes: Some(good.len()), ..config });
+        let mut frames = Vec::new();
+        for b in stream.iter().chain(&good) {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![1, 2, 3]; 2]);
+
+        // Without a budget the good frame is taken as part of the stalled one
+        let mut dec = BinCrc::<U512>::new();
+        let mut frames = 0;
+        dec.eat_bytes(&stream, &mut |_| frames += 1);
+        assert_eq!(frames, 0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "len8-only"))]
+    fn mixed_width_batch() {
+        let payloads: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; if i % 2 == 0 { 100 } else { 300 }]).collect();
+        let frames: Vec<&[u8]> = payloads.iter().map(|p| &p[..]).collect();
+        let mut buf = vec![0; 2500];
+        let written = BinCrc::<U512>::commit_frames(&frames, &mut buf).unwrap();
+        let size: usize = payloads.iter().map(|p| BinCrc::<U512>::size_hint(p.len()).unwrap()).sum();
+        assert_eq!(written, size);
+        assert_eq!((buf[0], buf[BinCrc::<U512>::size_hint(100).unwrap()]), (2, 3));
+
+        let mut dec = BinCrc::<U512>::new();
+        let mut decoded = Vec::new();
+        for b in &buf[..written] {
+            dec.eat_byte(*b, &mut |f| decoded.push(f.to_vec()));
+        }
+        assert_eq!(decoded, payloads);
+        assert_eq!(dec.stats().junk_bytes, 0);
+        assert!(matches!(BinCrc::<U512>::commit_frames(&frames, &mut buf[..1000]), Err(BinCrcError::NotEnoughSpace { .. })));
+    }
+
+    #[test]
+    fn backtrack_resync() {
+        // Length field hit by noise claims 40 bytes instead of 3
+        let mut corrupt = framed(&[0x10, 0x20, 0x30]);
+        corrupt[1] = 40;
+        let good = framed(&[1, 2]);
+        let stream = [&corrupt[..], &good, &framed(&[3; 8])].concat();
+
+        let config = BinCrcConfig { resync: Resync::Backtrack, ..Default::default() };
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut frames = Vec::new();
+        for b in &stream[..corrupt.len() + good.len()] {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![1, 2]]);
+        assert_eq!(dec.stats().backtracks, 1);
+        assert_eq!(dec.stats().junk_bytes, corrupt.len() as u64);
+        dec.eat_bytes(&stream[corrupt.len() + good.len()..], &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![vec![1, 2], vec![3; 8]]);
+        assert_eq!(dec.stats().backtracks, 1);
+
+        // Start byte resync waits for all 40 bytes, swallowing both good frames
+        let mut dec = BinCrc::<U64>::new();
+        let mut frames = 0;
+        dec.eat_bytes(&stream, &mut |_| frames += 1);
+        assert_eq!(frames, 0);
+
+        // Payload bytes that happen to be start and stop bytes don't break a good frame
+        let stream = [&framed(&[2, 1, 5, 0, 0, 3, 9]), &good[..]].concat();
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut frames = Vec::new();
+        dec.eat_bytes(&stream, &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![vec![2, 1, 5, 0, 0, 3, 9], vec![1, 2]]);
+        assert_eq!(dec.stats().backtracks, 0);
+    }
+
+    #[test]
+    fn crc_before_payload() {
+        let config = BinCrcConfig { crc_position: CrcPosition::BeforePayload, ..Default::default() };
+        let frame = BinCrc::<U64>::frame_with(&config, [7, 8, 9]).unwrap();
+        let crc = crc16::State::<crc16::XMODEM>::calculate(&[7, 8, 9]).to_be_bytes();
+        assert_eq!(frame, vec![2, 3, crc[0], crc[1], 7, 8, 9, 3]);
+        assert_eq!(BinCrc::<U64>::verify_frame_with(&config, &frame).unwrap(), 4..7);
+        assert!(BinCrc::<U64>::verify_frame(&frame).is_err());
+        let mut buf = [0; 16];
+        buf[..3].copy_from_slice(&[7, 8, 9]);
+        let written = BinCrc::<U64>::commit_in_place_with(&config, &mut buf, 0..3).unwrap();
+        assert_eq!(&buf[..written], &frame[..]);
+
+        let config = BinCrcConfig { checksum: Some(Checksum8::Xor), trailing_length_check: true, ..config };
+        let mut noisy = BinCrc::<U64>::frame_with(&config, [0x30; 12]).unwrap();
+        noisy[0] = 0x13; // start byte hit by noise
+        let stream = [&BinCrc::<U64>::frame_with(&config, [1, 2]).unwrap()[..], &noisy].concat();
+        let mut dec = BinCrc::<U64>::with_config(BinCrcConfig { resync: Resync::CrcAnchored, ..config });
+        let mut frames = Vec::new();
+        let mut stages = Vec::new();
+        for b in &stream {
+            stages.push(dec.bytes_needed());
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+        }
+        assert_eq!(frames, vec![vec![1, 2], vec![0x30; 12]]);
+        assert_eq!(stages[2..6], [Some(BytesNeeded::NeedTrailer(7)), Some(BytesNeeded::NeedTrailer(6)),
+                                  Some(BytesNeeded::NeedPayload(5)), Some(BytesNeeded::NeedPayload(4))]);
+    }
+
+    #[test]
+    fn max_occupancy() {
+        let stream = [&framed(&[1; 10])[..], &[0xAA; 4], &framed(&[2; 20]), &framed(&[3])].concat();
+        let mut dec = BinCrc::<U64>::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |_| ());
+        }
+        assert_eq!(dec.max_occupancy(), 20 + 5);
+        dec.reset_max_occupancy();
+        dec.eat_bytes(&framed(&[4; 3]), &mut |_| ());
+        assert_eq!(dec.max_occupancy(), 3 + 5);
+
+        // Junk claiming the longest frame that fits fills the buffer
+        let mut dec = BinCrc::<U64>::new();
+        dec.eat_bytes(&[2, 64 - 5], &mut |_| ());
+        dec.eat_bytes(&[0x55; 100], &mut |_| ());
+        assert_eq!(dec.max_occupancy(), 64);
+    }
+
+    #[test]
+    fn fixed_payload() {
+        let config = BinCrcConfig { length_width: LengthWidth::FixedPayload(4), ..Default::default() };
+        let frame = BinCrc::<U64>::frame_with(&config, [2, 3, 4, 5]).unwrap();
+        let crc = crc16::State::<crc16::XMODEM>::calculate(&[2, 3, 4, 5]).to_be_bytes();
+        assert_eq!(frame, vec![2, 2, 3, 4, 5, crc[0], crc[1], 3]);
+        assert!(matches!(BinCrc::<U64>::frame_with(&config, [1, 2, 3]), Err(BinCrcError::InvalidLength)));
+        assert!(matches!(BinCrc::<U64>::size_hint_with(&config, 5), Err(BinCrcError::InvalidLength)));
+
+        let stream = [&frame[..], &[0xEE, 2], &frame, &framed(&[1, 2, 3, 4])].concat();
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut frames = Vec::new();
+        let mut stages = Vec::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+            stages.push(dec.bytes_needed());
+        }
+        assert_eq!(frames, vec![vec![2, 3, 4, 5]; 2]);
+        assert_eq!(stages[..2], [Some(BytesNeeded::NeedPayload(7)), Some(BytesNeeded::NeedPayload(6))]);
+
+        // Frames are found by CRC when the start byte is lost
+        let config = BinCrcConfig { resync: Resync::CrcAnchored, ..config };
+        let mut noisy = frame.clone();
+        noisy[0] = 0x13;
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut frames = Vec::new();
+        dec.eat_bytes(&[&frame[..], &noisy].concat(), &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![vec![2, 3, 4, 5]; 2]);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn without_stop_byte() {
+        let config = BinCrcConfig { omit_stop_byte: true, ..Default::default() };
+        assert_eq!(BinCrc::<U512>::size_hint_with(&config, 3).unwrap(), BinCrc::<U512>::size_hint(3).unwrap() - 1);
+        let frame = BinCrc::<U512>::frame_with(&config, [7, 8, 9]).unwrap();
+        assert_eq!(frame, framed(&[7, 8, 9])[..7]);
+        assert_eq!(BinCrc::<U512>::verify_frame_with(&config, &frame).unwrap(), 2..5);
+
+        let payloads: Vec<Vec<u8>> = (1..=300usize).step_by(23).map(|len| vec![len as u8; len]).collect();
+        let mut stream = Vec::new();
+        for payload in &payloads {
+            stream.extend_from_slice(&BinCrc::<U512>::frame_with(&config, payload).unwrap());
+            stream.extend_from_slice(&[0xEE, 3]);
+        }
+        for &config in &[config, BinCrcConfig { trailing_length_check: true, checksum: Some(Checksum8::Sum), ..config }] {
+            let stream: Vec<u8> = payloads.iter()
+                .flat_map(|p| [vec![0xEE, 3], BinCrc::<U512>::frame_with(&config, p).unwrap()].concat())
+                .collect();
+            let mut dec = BinCrc::<U512>::with_config(config);
+            let mut frames = Vec::new();
+            for b in &stream {
+                dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+            }
+            assert_eq!(frames, payloads);
+            assert_eq!(dec.stats().junk_bytes, 2 * payloads.len() as u64);
+        }
+
+        // Frames with a stop byte don't pass, the last byte is taken as the start of the next one
+        let mut dec = BinCrc::<U512>::new();
+        let mut frames = 0;
+        dec.eat_bytes(&stream, &mut |_| frames += 1);
+        assert_eq!(frames, 0);
+    }
+
+    #[test]
+    fn max_junk_retained() {
+        let mut late = framed(&[0x30; 6]);
+        late[0] = 0x22; // start byte hit by noise
+        let stream = [&[0xEE; 100][..], &late].concat();
+
+        let decode = |max_junk_retained| {
+            let config = BinCrcConfig { resync: Resync::CrcAnchored, max_junk_retained, ..Default::default() };
+            let mut dec = BinCrc::<U64>::with_config(config);
+            let mut frames = Vec::new();
+            let mut retained = 0;
+            for b in &stream {
+                dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+                retained = retained.max(dec.write_idx - dec.anchor_floor);
+            }
+            (frames, retained)
+        };
+        assert_eq!(decode(None), (vec![vec![0x30; 6]], 64));
+        assert_eq!(decode(Some(late.len())), (vec![vec![0x30; 6]], late.len()));
+        // Too little kept to find the frame, pending bytes of the frame are kept on top
+        let (frames, retained) = decode(Some(4));
+        assert!(frames.is_empty());
+        assert!(retained < late.len());
+    }
+
+    #[test]
+    fn typed_frames() {
+        let mut buf = [0; 32];
+        let written = BinCrc::<U64>::commit_typed(0x81, &[1, 2, 3], &mut buf).unwrap();
+        assert_eq!(&buf[..written], &framed(&[0x81, 1, 2, 3])[..]);
+        let mut stream = buf[..written].to_vec();
+        let written = BinCrc::<U64>::commit_typed(0x82, &[], &mut buf).unwrap();
+        stream.extend_from_slice(&buf[..written]);
+        assert!(matches!(BinCrc::<U64>::commit_typed(1, &[0; 40], &mut buf), Err(BinCrcError::NotEnoughSpace { .. })));
+
+        let mut dec = BinCrc::<U64>::new();
+        let mut frames = Vec::new();
+        for b in &stream {
+            dec.eat_byte_typed(*b, &mut |msg_type, payload| frames.push((msg_type, payload.to_vec())));
+        }
+        assert_eq!(frames, vec![(0x81, vec![1, 2, 3]), (0x82, vec![])]);
+        assert!(matches!(BinCrc::<U64>::commit_typed(0x100, &[1], &mut buf), Err(BinCrcError::InvalidLength)));
+    }
+
+    #[test]
+    fn wide_typed_frames() {
+        use crate::{ByteOrder, TypeWidth};
+
+        let mut buf = [0; 32];
+        let config = BinCrcConfig { type_width: TypeWidth::U16, type_byte_order: ByteOrder::LittleEndian, ..Default::default() };
+        let written = BinCrc::<U64>::commit_typed_with(&config, 0x1234, &[1, 2], &mut buf).unwrap();
+        assert_eq!(&buf[..written], &framed(&[0x34, 0x12, 1, 2])[..]);
+        let mut stream = [&buf[..written], &framed(&[9])[..]].concat();
+        let config32 = BinCrcConfig { type_width: TypeWidth::U32, ..Default::default() };
+        let written = BinCrc::<U64>::commit_typed_with(&config32, 0xA1B2_C3D4, &[5], &mut buf).unwrap();
+        assert_eq!(&buf[..written], &framed(&[0xA1, 0xB2, 0xC3, 0xD4, 5])[..]);
+        stream.extend_from_slice(&buf[..written]);
+
+        // Frame of one byte is too short for the type field, the last one is taken as 16 bit too
+        let mut dec = BinCrc::<U64>::with_config(config);
+        let mut frames = Vec::new();
+        let mut raw = Vec::new();
+        for b in &stream {
+            dec.eat_byte_typed(*b, &mut |msg_type, payload| frames.push((msg_type, payload.to_vec())));
+        }
+        // Short frame is junk, not a frame
+        assert_eq!(dec.stats().frames, 2);
+        assert_eq!(dec.stats().junk_bytes, framed(&[9]).len() as u64);
+        let mut dec = BinCrc::<U64>::with_config(config);
+        for b in &stream {
+            dec.eat_byte_typed_raw(*b, &mut |msg_type, _| raw.push(msg_type.to_vec()));
+        }
+        assert_eq!(frames, vec![(0x1234, vec![1, 2]), (0xB2A1, vec![0xC3, 0xD4, 5])]);
+        assert_eq!(raw, vec![vec![0x34, 0x12], vec![0xA1, 0xB2]]);
+
+        let untyped = BinCrcConfig { type_width: TypeWidth::None, ..Default::default() };
+        let written = BinCrc::<U64>::commit_typed_with(&untyped, 0, &[7, 8], &mut buf).unwrap();
+        assert_eq!(&buf[..written], &framed(&[7, 8])[..]);
+        assert!(matches!(BinCrc::<U64>::commit_typed_with(&untyped, 1, &[7], &mut buf), Err(BinCrcError::InvalidLength)));
+        assert!(matches!(BinCrc::<U64>::commit_typed_with(&config, 0x1_0000, &[7], &mut buf), Err(BinCrcError::InvalidLength)));
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn largest_length_values() {
+        struct Greedy;
+        impl FrameHandler for Greedy {
+            fn decode_frame(&self, _data: &[u8]) -> HandlerResult {
+                HandlerResult::NeedMoreBytes(usize::MAX)
+            }
+        }
+        static GREEDY: Greedy = Greedy;
+        let headers: [&[u8]; 5] = [&[3, 0xFF, 0xFF], &[4, 0xFF, 0xFF, 0xFF], &[2, 0xFF], &[2, 0xFF, 0xFF, 0x03], &[0x10, 0xFF]];
+        let configs = [
+            BinCrcConfig::default(),
+            BinCrcConfig { length_width: LengthWidth::U16, length_unit: crate::LengthUnit::Words16, ..Default::default() },
+            BinCrcConfig { length_width: LengthWidth::Varint, trailing_length_check: true, ..Default::default() },
+            BinCrcConfig { crc_position: crate::CrcPosition::BeforePayload, omit_stop_byte: true, ..Default::default() }
+        ];
+        for config in &configs {
+            for header in &headers {
+                let mut stream = header.to_vec();
+                stream.extend_from_slice(&[0xFF; 70]);
+                stream.extend_from_slice(&BinCrc::<U64>::frame_with(config, [1, 2]).unwrap());
+                let mut frames = Vec::new();
+                let mut dec = BinCrc::<U64>::with_config(*config);
+                dec.register_handler(0x10, &GREEDY).unwrap();
+                for b in &stream {
+                    dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+                }
+                assert_eq!(frames, vec![vec![1, 2]]);
+                assert!(BinCrc::<U64>::verify_frame_with(config, &stream).is_err());
+                // Largest capacity there is, nothing wraps around
+                let parsed = frame::parse::<DynLen>(config, usize::MAX, header);
+                assert!(matches!(parsed, Parsed::NeedMoreBytes(_) | Parsed::Invalid(_) | Parsed::TooBig { .. }));
+            }
+        }
+    }
+
+    #[test]
+    fn min_frame_len() {
+        for framing in &[Framing::LengthPrefixed, Framing::Delimited] {
+            let config = BinCrcConfig { framing: *framing, min_frame_len: 4, ..Default::default() };
+            let mut stream = Vec::new();
+            for len in 1..=5 {
+                stream.extend(BinCrc::<U64>::frame_with(&config, vec![len as u8; len]).unwrap());
+            }
+            let mut frames = Vec::new();
+            let mut dec = BinCrc::<U64>::with_config(config);
+            dec.eat_bytes(&stream, &mut |f| frames.push(f.to_vec()));
+            assert_eq!(frames, vec![vec![4; 4], vec![5; 5]]);
+            assert_eq!(dec.stats().crc_errors, 0);
+            assert!(BinCrc::<U64>::verify_frame_with(&config, &BinCrc::<U64>::frame_with(&config, [3; 3]).unwrap()).is_err());
+            assert!(config.min_wire_len() <= BinCrc::<U64>::frame_with(&config, [4; 4]).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn mismatched_config() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use crate::{BinCrcEncoder, CrcBackend};
+
+        struct Runs(AtomicUsize);
+        impl SyncObserver for Runs {
+            fn resynced(&self, _skipped_bytes: usize) {}
+            fn crc_failure_run(&self, failed_frames: usize) {
+                assert_eq!(failed_frames, CRC_FAILURE_RUN);
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        static RUNS: Runs = Runs(AtomicUsize::new(0));
+        struct Inverted;
+        impl crate::Crc16Backend for Inverted {
+            fn init(&self) -> u16 { 0 }
+            fn update(&self, state: u16, data: &[u8]) -> u16 { crc16::State::<crc16::XMODEM>::calculate(data) ^ state }
+            fn finish(&self, state: u16) -> u16 { !state }
+        }
+        static INVERTED: Inverted = Inverted;
+
+        let other = BinCrcConfig { crc_backend: CrcBackend(&INVERTED), ..Default::default() };
+        let encoder = BinCrcEncoder::<U64>::with_config(other);
+        let mut dec = BinCrc::<U64>::with_config(BinCrcConfig { resync: Resync::CrcAnchored, max_junk_run: Some(4), ..Default::default() });
+        assert!(!dec.config_matches(encoder.config()));
+        assert!(!encoder.config_matches(dec.config()));
+        assert!(dec.config_matches(&BinCrcConfig::default()));
+        assert!(BinCrcEncoder::<U64>::new().config_matches(dec.config()));
+
+        dec.set_observer(&RUNS);
+        let mut buf = [0; 16];
+        for i in 0..2 * CRC_FAILURE_RUN {
+            let len = encoder.commit_frame(&[i as u8 + 1; 4], &mut buf).unwrap();
+            dec.eat_bytes(&buf[..len], &mut |_| panic!("no frame passes CRC"));
+        }
+        assert!(dec.stats().crc_errors >= 2 * CRC_FAILURE_RUN as u64);
+        assert_eq!(RUNS.0.load(Ordering::Relaxed), 1);
+    }
+
+    // Frame preceded by a bogus header that holds it at offset o until the buffer end is reached,
+    // so that compaction moves it while the byte at index N - o of the frame is being stored
+    fn wrap_at_every_index<N: ArrayLength<u8>>(config: BinCrcConfig) {
+        let n = N::to_usize();
+        for p in 1..=n - 5 {
+            let frame = BinCrc::<N>::frame_with(&config, vec![0x55; p]).unwrap();
+            if frame.len() > n {
+                continue;
+            }
+            for o in (n + 1).saturating_sub(frame.len()).max(2)..n {
+                let mut stream = vec![0xEE; o];
+                stream[..2].copy_from_slice(&[2, (n - 5) as u8]);
+                stream.extend_from_slice(&frame);
+                let mut frames = Vec::new();
+                let mut dec = BinCrc::<N>::with_config(config);
+                for b in &stream {
+                    dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+                }
+                let mut bulk = Vec::new();
+                BinCrc::<N>::with_config(config).eat_bytes(&stream, &mut |f| bulk.push(f.to_vec()));
+                assert_eq!(frames, vec![vec![0x55; p]], "N {} payload {} offset {} {:?}", n, p, o, config);
+                assert_eq!(bulk, frames);
+            }
+        }
+    }
+
+    #[test]
+    fn wrap_boundary() {
+        use crate::typenum::{U100, U16, U17};
+        let configs = [
+            BinCrcConfig::default(),
+            BinCrcConfig { crc_includes_stop: true, ..Default::default() },
+            BinCrcConfig { crc_position: CrcPosition::BeforePayload, ..Default::default() },
+            BinCrcConfig { checksum: Some(Checksum8::Xor), ..Default::default() },
+            BinCrcConfig { resync: Resync::CrcAnchored, ..Default::default() },
+        ];
+        for config in &configs {
+            wrap_at_every_index::<U16>(*config);
+            wrap_at_every_index::<U17>(*config);
+            wrap_at_every_index::<U64>(*config);
+        }
+        wrap_at_every_index::<U100>(BinCrcConfig::default());
+    }
+
+    #[test]
+    fn buffer_overflow_event() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        struct Overflows(AtomicUsize);
+        impl SyncObserver for Overflows {
+            fn resynced(&self, _skipped_bytes: usize) {}
+            fn buffer_overflow(&self, discarded: &[u8]) {
+                assert_eq!(discarded.len(), 64);
+                assert_eq!(discarded[..3], [2, 0xFF, 0xAA]);
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        static OVERFLOWS: Overflows = Overflows(AtomicUsize::new(0));
+
+        let mut stream = vec![2, 0xFF, 0xAA];
+        stream.resize(64, 0xAA);
+        let mut dec = BinCrc::<U64>::new();
+        dec.set_observer(&OVERFLOWS);
+        assert_eq!(dec.fill(&stream), 64);
+        let mut frames = Vec::new();
+        dec.eat_bytes(&framed(&[4]), &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![vec![4]]);
+        assert_eq!(OVERFLOWS.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn running_crc() {
+        let payload: Vec<u8> = (0..200u32).map(|i| (i * 7) as u8).collect();
+        let configs = [
+            BinCrcConfig::default(),
+            BinCrcConfig { crc_includes_stop: true, ..Default::default() },
+            BinCrcConfig { crc_position: CrcPosition::BeforePayload, ..Default::default() },
+            BinCrcConfig { length_width: LengthWidth::Varint, address_len: 1, ..Default::default() },
+        ];
+        for config in &configs {
+            let frame = match config.address_len {
+                0 => BinCrc::<U512>::frame_with(config, &payload).unwrap(),
+                _ => {
+                    let mut buf = vec![0; 300];
+                    let len = BinCrc::<U512>::commit_frame_addressed_with(config, &[9], &payload, &mut buf).unwrap();
+                    buf[..len].to_vec()
+                }
+            };
+            for &split in &[2, 6, 50, 150, frame.len() - 3, frame.len() - 1] {
+                let mut frames = Vec::new();
+                let mut dec = BinCrc::<U512>::with_config(*config);
+                dec.eat_bytes(&frame[..split], &mut |f| frames.push(f.to_vec()));
+                if split >= 50 {
+                    let (covered, crc) = dec.running_crc().unwrap();
+                    assert!(covered > 0 && covered <= payload.len());
+                    assert_eq!(crc, crc16::State::<crc16::XMODEM>::calculate(&payload[..covered]) as u32);
+                }
+                dec.eat_bytes(&frame[split..], &mut |f| frames.push(f.to_vec()));
+                assert_eq!(frames, vec![payload.clone()]);
+                assert_eq!(dec.running_crc(), None);
+
+                // Corruption in the covered part is still caught
+                let mut bad = frame.clone();
+                bad[10] ^= 0x40;
+                let mut dec = BinCrc::<U512>::with_config(*config);
+                dec.eat_bytes(&bad[..split], &mut |_| panic!("corrupted frame"));
+                dec.eat_bytes(&bad[split..], &mut |_| panic!("corrupted frame"));
+                assert_eq!(dec.stats().crc_errors, 1);
+            }
+        }
+    }
+
+    #[cfg(feature = "allocator-api")]
+    #[test]
+    fn custom_allocator() {
+        use core::alloc::{AllocError, Layout};
+        use core::ptr::NonNull;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        // Counts allocations, passes them on to the global allocator
+        struct Pool(AtomicUsize);
+        unsafe impl Allocator for &Pool {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                std::alloc::Global.allocate(layout)
+            }
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                unsafe { std::alloc::Global.deallocate(ptr, layout) }
+            }
+        }
+        let pool = Pool(AtomicUsize::new(0));
+
+        let frame = BinCrc::<U64>::frame_in([1, 2, 3], &pool).unwrap();
+        assert_eq!(frame[..], framed(&[1, 2, 3])[..]);
+        let mut dec = BinCrc::<U64>::new();
+        dec.fill(&[&frame[..], &frame[..], &frame[..2]].concat());
+        let frames: Vec<_> = dec.drain_in(&pool).collect();
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|f| f[..] == [1, 2, 3]));
+        assert_eq!(pool.0.load(Ordering::Relaxed), 3);
+    }
+
+    #[cfg(feature = "last-frame")]
+    #[test]
+    fn last_frame() {
+        let mut dec = BinCrc::<U64>::new();
+        assert_eq!(dec.last_frame(), None);
+        let stream = [framed(&[1, 2, 3]), framed(&[4; 20])].concat();
+        let mut frames = 0;
+        dec.eat_bytes(&stream[..8], &mut |_| frames += 1);
+        assert_eq!(dec.last_frame(), Some(&[1, 2, 3][..]));
+        // Kept while the next frame is coming in and replaced once it is there
+        dec.eat_bytes(&stream[8..20], &mut |_| frames += 1);
+        assert_eq!(dec.last_frame(), Some(&[1, 2, 3][..]));
+        dec.eat_bytes(&stream[20..], &mut |_| frames += 1);
+        assert_eq!(dec.last_frame(), Some(&[4; 20][..]));
+        assert_eq!(frames, 2);
+        dec.reset();
+        assert_eq!(dec.last_frame(), None);
+    }
+
+    #[test]
+    fn every_byte_value() {
+        let all: Vec<u8> = (0..=255).collect();
+        // Two frames still cover every value where 255 bytes is the longest payload
+        let mut payloads: Vec<Vec<u8>> = if LEN8_ONLY { all.chunks(128).map(|c| c.to_vec()).collect() } else { vec![all] };
+        payloads.push([0x02, 0x03, 0x04].iter().cycle().take(200).copied().collect());
+        for payload in &payloads {
+            let mut frame = vec![0; BinCrc::<U512>::size_hint(payload.len()).unwrap()];
+            BinCrc::<U512>::commit_frame(payload, &mut frame).unwrap();
+            let mut frames = Vec::new();
+            let mut dec = BinCrc::<U512>::new();
+            for b in &frame {
+                dec.eat_byte(*b, &mut |f| frames.push(f.to_vec()));
+            }
+            assert_eq!(frames, vec![payload.clone()]);
+            assert_eq!(dec.stats().junk_bytes, 0);
+        }
+    }
+
+    #[test]
+    fn datagram() {
+        let datagram = [framed(&[1, 2]), framed(&[3; 10]), framed(&[4])].concat();
+        let mut frames = Vec::new();
+        assert_eq!(BinCrc::<U64>::decode_datagram(&datagram, &mut |f| frames.push(f.to_vec())).unwrap(), 3);
+        assert_eq!(frames, vec![vec![1, 2], vec![3; 10], vec![4]]);
+        assert_eq!(BinCrc::<U64>::decode_datagram(&[], &mut |_| panic!()).unwrap(), 0);
+
+        frames.clear();
+        let cut = &datagram[..datagram.len() - 1];
+        assert!(matches!(
+            BinCrc::<U64>::decode_datagram(cut, &mut |f| frames.push(f.to_vec())),
+            Err(BinCrcError::UnexpectedEof)
+        ));
+        assert_eq!(frames.len(), 2);
+        let mut bad = datagram.clone();
+        bad[3] ^= 1;
+        assert!(matches!(BinCrc::<U64>::decode_datagram(&bad, &mut |_| ()), Err(BinCrcError::CrcMismatch)));
+
+        let config = BinCrcConfig { framing: Framing::Delimited, ..Default::default() };
+        let payload = [0x7E, 0x7D, 2, 3];
+        let datagram = [BinCrc::<U64>::frame_with(&config, payload).unwrap(), BinCrc::<U64>::frame_with(&config, [5]).unwrap()].concat();
+        frames.clear();
+        assert_eq!(BinCrc::<U64>::decode_datagram_with(&config, &datagram, &mut |f| frames.push(f.to_vec())).unwrap(), 2);
+        assert_eq!(frames, vec![payload.to_vec(), vec![5]]);
+        // Longer than the buffer, even though all of it is there
+        let long = BinCrc::<U512>::frame_with(&config, [0x55; 100]).unwrap();
+        assert!(matches!(BinCrc::<U64>::decode_datagram_with(&config, &long, &mut |_| panic!()), Err(BinCrcError::BadLength)));
+    }
+
+    #[test]
+    fn next_start_byte_resync() {
+        // Frames failing CRC leave their junk payload buffered to be looked through again
+        let mut stream = vec![0x55; 20];
+        for i in 0..30u8 {
+            let mut frame = framed(&[i % 4 + 5; 40]);
+            if i % 3 == 0 {
+                frame[10] ^= 1;
+            }
+            stream.extend(frame);
+            stream.extend_from_slice(&[0xAA, 0x03, 0x55]);
+        }
+        let decode = |resync, chunk| {
+            let mut dec = BinCrc::<U64>::with_config(BinCrcConfig { resync, idle_byte: Some(0xAA), ..Default::default() });
+            let mut frames = Vec::new();
+            for bytes in stream.chunks(chunk) {
+                dec.eat_bytes(bytes, &mut |f| frames.push(f.to_vec()));
+            }
+            (frames, *dec.stats())
+        };
+        for &chunk in &[1, 7, 64] {
+            let (frames, stats) = decode(Resync::NextStartByte, chunk);
+            assert_eq!(frames.len(), 20);
+            assert_eq!((frames, stats), decode(Resync::StartByte, chunk));
+        }
+    }
+
+    #[test]
+    fn frame_ranges() {
+        let mut bad = framed(&[9; 6]);
+        bad[4] ^= 1;
+        let parts = [vec![0x55, 0x02], framed(&[1, 2]), bad, framed(&[3; 10]), vec![0xAA], framed(&[4])];
+        let buf = parts.concat();
+        let ranges = BinCrc::<U64>::frame_ranges(&buf);
+        assert_eq!(ranges, vec![2..9, 20..35, 36..42]);
+        for (range, payload) in ranges.iter().zip(&[vec![1, 2], vec![3; 10], vec![4]]) {
+            let frame = &buf[range.clone()];
+            assert_eq!(frame[BinCrc::<U64>::verify_frame(frame).unwrap()], payload[..]);
+        }
+        assert!(BinCrc::<U64>::frame_ranges(&buf[..8]).is_empty());
+    }
+
+    #[test]
+    fn crc_widths() {
+        use crate::CrcWidth;
+
+        let check = BinCrc::<U64>::frame_with(&BinCrcConfig { crc_width: CrcWidth::Crc32, ..Default::default() }, b"123456789").unwrap();
+        assert_eq!(check[11..], [0xCB, 0xF4, 0x39, 0x26, 3]);
+        let check = BinCrc::<U64>::frame_with(&BinCrcConfig { crc_width: CrcWidth::Crc8, ..Default::default() }, b"123456789").unwrap();
+        assert_eq!(check[11..], [0xF4, 3]);
+
+        let layouts = [
+            BinCrcConfig::default(),
+            BinCrcConfig { crc_position: CrcPosition::BeforePayload, checksum: Some(Checksum8::Xor), ..Default::default() },
+            BinCrcConfig { crc_includes_stop: true, trailing_length_check: true, ..Default::default() },
+            BinCrcConfig { omit_stop_byte: true, ..Default::default() },
+            BinCrcConfig { framing: Framing::Delimited, ..Default::default() }
+        ];
+        for layout in &layouts {
+            for &crc_width in &[CrcWidth::Crc8, CrcWidth::Crc16, CrcWidth::Crc32] {
+                let config = BinCrcConfig { crc_width, ..*layout };
+                let payloads = [vec![3, 2, 0x10], vec![0x55; 40]];
+                let mut stream = vec![0x55];
+                for payload in &payloads {
+                    let frame = BinCrc::<U64>::frame_with(&config, payload).unwrap();
+                    if config.framing != Framing::Delimited {
+                        assert_eq!(frame.len(), BinCrc::<U64>::size_hint_with(&BinCrcConfig { crc_width: CrcWidth::Crc16, ..config }, payload.len()).unwrap() + crc_width.bytes() - 2);
+                    }
+                    stream.extend(frame);
+                }
+                let mut bad = BinCrc::<U64>::frame_with(&config, [7; 5]).unwrap();
+                let mid = bad.len() / 2;
+                bad[mid] ^= 0x40;
+                stream.extend(bad);
+
+                let mut frames = Vec::new();
+                let mut suspects = 0;
+                let mut dec = BinCrc::<U64>::with_config(config);
+                for chunk in stream.chunks(5) {
+                    for b in chunk {
+                        dec.eat_byte_with_suspect(*b, &mut |f| frames.push(f.to_vec()), &mut |_| suspects += 1);
+                    }
+                }
+                assert_eq!(frames, payloads, "{:?}", config);
+                assert_eq!(suspects, 1, "{:?}", config);
+            }
+        }
+    }
+
+    #[test]
+    fn frame_bytes() {
+        let frame = BinCrc::<U64>::frame_bytes([1, 2, 3]).unwrap();
+        let copy = frame.clone();
+        assert_eq!(copy.as_ptr(), frame.as_ptr());
+        assert_eq!(frame, BinCrc::<U64>::frame([1, 2, 3]).unwrap());
+        let config = BinCrcConfig { framing: Framing::Delimited, ..Default::default() };
+        assert_eq!(BinCrc::<U64>::frame_bytes_with(&config, [2, 3]).unwrap(), BinCrc::<U64>::frame_with(&config, [2, 3]).unwrap());
+    }
+
+    // Known frames with junk, a frame failing CRC and stray start bytes in between, fed in every
+    // split in two and in random chunks, through eat_bytes and Decoder::decode
+    fn chunking_agnostic<N: ArrayLength<u8>>() {
+        let n = N::to_usize();
+        let mut stream = Vec::new();
+        for i in 0..30usize {
+            let payload: Vec<u8> = (0..i * 7 % (n - 5) + 1).map(|b| (b * 3 + i) as u8).collect();
+            let mut frame = BinCrc::<N>::frame(&payload).unwrap();
+            match i % 5 {
+                0 => stream.extend_from_slice(&[0x55, 0x02]),
+                1 => {
+                    let last = frame.len() - 2;
+                    frame[last] ^= 1;
+                },
+                2 => stream.extend_from_slice(&[0x03, 0x00, 0x02]),
+                _ => {}
+            }
+            stream.extend(frame);
+        }
+        let mut expected = Vec::new();
+        let mut dec = BinCrc::<N>::new();
+        for b in &stream {
+            dec.eat_byte(*b, &mut |f| expected.push(f.to_vec()));
+        }
+        assert!(expected.len() >= 20);
+
+        let eat_chunks = |splits: &[usize]| {
+            let mut frames = Vec::new();
+            let mut dec = BinCrc::<N>::new();
+            let mut from = 0;
+            for &to in splits.iter().chain(Some(&stream.len())) {
+                dec.eat_bytes(&stream[from..to], &mut |f| frames.push(f.to_vec()));
+                from = to;
+            }
+            frames
+        };
+        for split in 0..=stream.len() {
+            assert_eq!(eat_chunks(&[split]), expected, "N = {}, split at {}", n, split);
+        }
+
+        // xorshift, so that failures reproduce
+        let mut seed = 0x2545_F491u32;
+        let mut random = move |below: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize % below
+        };
+        for _ in 0..50 {
+            let mut splits = Vec::new();
+            let mut at = 0;
+            while at < stream.len() {
+                at = (at + 1 + random(2 * n)).min(stream.len());
+                splits.push(at);
+            }
+            assert_eq!(eat_chunks(&splits), expected, "N = {}, splits {:?}", n, splits);
+
+            let mut frames = Vec::new();
+            let mut dec = BinCrc::<N>::new();
+            let mut acc = BytesMut::new();
+            let mut from = 0;
+            for &to in &splits {
+                acc.extend_from_slice(&stream[from..to]);
+                from = to;
+                while let Some(batch) = dec.decode(&mut acc).unwrap() {
+                    frames.extend(batch);
+                }
+            }
+            assert_eq!(frames, expected, "N = {}, decode splits {:?}", n, splits);
+        }
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        use crate::typenum::{U16, U300};
+
+        chunking_agnostic::<U16>();
+        chunking_agnostic::<U64>();
+        chunking_agnostic::<U300>();
+    }
+
+    #[test]
+    fn decode_within_write_idx() {
+        let frame = framed(&[1, 2, 3, 4]);
+        let mut dec = BinCrc::<U64>::new();
+        let mut frames = 0;
+        dec.eat_bytes(&frame, &mut |_| frames += 1);
+        // Rest of the frame is still in the buffer from before, but not received again
+        dec.eat_bytes(&frame[..3], &mut |_| frames += 1);
+        assert_eq!(frames, 1);
+        assert_eq!(dec.decode_frame(0, frame.len()), DecodeResult::NeedMoreBytes(frame.len() - 3));
+        assert_eq!(dec.decode_frame(0, 64), DecodeResult::NeedMoreBytes(frame.len() - 3));
+        assert_eq!(dec.decode_frame(10, 64), DecodeResult::NeedMoreBytes(1));
+    }
+
+    #[test]
+    fn truncated_frames() {
+        let configs = [
+            BinCrcConfig::default(),
+            BinCrcConfig { trailing_length_check: true, checksum: Some(Checksum8::Sum), ..Default::default() },
+            BinCrcConfig { crc_position: CrcPosition::BeforePayload, omit_stop_byte: true, ..Default::default() },
+            BinCrcConfig { length_width: LengthWidth::Varint, crc_width: crate::CrcWidth::Crc32, ..Default::default() },
+            BinCrcConfig { address_len: 2, ..Default::default() }
+        ];
+        for config in &configs {
+            let mut frame = vec![0; 80];
+            let len = if config.address_len == 0 {
+                BinCrc::<U64>::commit_frame_with(config, &[5; 20], &mut frame).unwrap()
+            } else {
+                BinCrc::<U64>::commit_frame_addressed_with(config, &[7, 8], &[5; 20], &mut frame).unwrap()
+            };
+            for cut in 0..len {
+                assert!(matches!(frame::parse::<DynLen>(config, 64, &frame[..cut]), Parsed::NeedMoreBytes(_)), "{:?} cut at {}", config, cut);
+            }
+            assert!(matches!(frame::parse::<DynLen>(config, 64, &frame[..len]), Parsed::Frame { count, .. } if count == len));
+        }
+    }
+
+    #[test]
+    fn event_handler() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = |events: &Arc<Mutex<Vec<DecoderEvent>>>| {
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event)
+        };
+
+        let mut bad = framed(&[1, 2, 3]);
+        bad[3] ^= 1;
+        let mut stream = vec![0xAA, 0xBB, 2, 100];
+        stream.extend_from_slice(&bad);
+        stream.extend_from_slice(&framed(&[4]));
+        let mut dec = BinCrc::<U64>::new();
+        dec.set_event_handler(log(&events));
+        let mut frames = Vec::new();
+        dec.eat_bytes(&stream, &mut |f| frames.push(f.to_vec()));
+        assert_eq!(frames, vec![vec![4]]);
+
+        let logged = core::mem::take(&mut *events.lock().unwrap());
+        assert!(logged.contains(&DecoderEvent::OversizedFrame { claimed_len: 100 }));
+        assert_eq!(logged.iter().filter(|e| matches!(e, DecoderEvent::CrcError { expected, got } if expected != got)).count(), 1);
+        let junk: usize = logged.iter().map(|e| match e {
+            DecoderEvent::JunkBytes { count } => *count,
+            _ => 0
+        }).sum();
+        assert_eq!(junk, 4 + bad.len());
+        assert_eq!(logged.last(), Some(&DecoderEvent::Resynced { dropped: junk }));
+
+        let mut dec = BinCrc::<U64>::new();
+        dec.set_event_handler(log(&events));
+        assert_eq!(dec.fill(&[0xAA; 64]), 64);
+        dec.eat_byte(0xAA, &mut |_| {});
+        assert_eq!(events.lock().unwrap()[0], DecoderEvent::BufferOverflow { discarded: 64 });
+    }
+}
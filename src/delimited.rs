@@ -0,0 +1,206 @@
+use core::mem::MaybeUninit;
+use core::ops::Range;
+
+use crate::{BinCrcConfig, BinCrcError};
+use crate::frame::Parsed;
+
+const START: u8 = 2;
+const STOP: u8 = 3;
+// Escaped byte follows, xored with ESCAPE_XOR
+const ESC: u8 = 0x10;
+const ESCAPE_XOR: u8 = 0x20;
+
+fn needs_escape(byte: u8) -> bool {
+    byte == START || byte == STOP || byte == ESC
+}
+
+fn escaped_len(data: &[u8]) -> usize {
+    data.len() + data.iter().filter(|b| needs_escape(**b)).count()
+}
+
+// Largest frame a frame_len payload can turn into, when every byte needs escaping
+pub(crate) fn max_len(config: &BinCrcConfig, frame_len: usize) -> usize {
+    config.preamble_len() + 1 + 2 * (frame_len + config.crc_len()) + 1
+}
+
+pub(crate) fn commit_uninit(
+    config: &BinCrcConfig,
+    frame: &[u8],
+    buf: &mut [MaybeUninit<u8>]
+) -> Result<usize, BinCrcError>
+{
+    let crc = config.crc(frame).to_be_bytes();
+    let crc = &crc[4 - config.crc_len()..];
+    let len = config.preamble_len() + 1 + escaped_len(frame) + escaped_len(crc) + 1;
+    if buf.len() < len {
+        return Err(BinCrcError::NotEnoughSpace { required: len, available: buf.len() });
+    }
+    let mut idx = write_head(config, buf);
+    for b in frame.iter().chain(crc) {
+        idx = write_escaped(buf, idx, *b);
+    }
+    buf[idx] = MaybeUninit::new(STOP);
+    Ok(idx + 1)
+}
+
+// Escaping makes the frame longer than the payload, so the payload is first moved to the end
+// of a worst case sized frame and escaped from there forward, the write position never
+// overtakes the read position.
+pub(crate) fn commit_in_place(
+    config: &BinCrcConfig,
+    buf: &mut [u8],
+    payload: Range<usize>
+) -> Result<usize, BinCrcError>
+{
+    let frame_len = payload.len();
+    let worst_len = max_len(config, frame_len);
+    if buf.len() < worst_len {
+        return Err(BinCrcError::NotEnoughSpace { required: worst_len, available: buf.len() });
+    }
+    let src = worst_len - frame_len;
+    buf.copy_within(payload, src);
+    let crc = config.crc(&buf[src..worst_len]).to_be_bytes();
+    let crc = &crc[4 - config.crc_len()..];
+    // Only initialized values are written, same as in encoder::commit_frame
+    let buf = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    let mut idx = write_head(config, buf);
+    for i in src..worst_len {
+        let byte = unsafe { buf[i].assume_init() };
+        idx = write_escaped(buf, idx, byte);
+    }
+    for b in crc {
+        idx = write_escaped(buf, idx, *b);
+    }
+    buf[idx] = MaybeUninit::new(STOP);
+    Ok(idx + 1)
+}
+
+fn write_head(config: &BinCrcConfig, buf: &mut [MaybeUninit<u8>]) -> usize {
+    let preamble_len = config.preamble_len();
+    if let Some(preamble) = config.preamble {
+        buf[..preamble_len].iter_mut().for_each(|b| *b = MaybeUninit::new(preamble.byte));
+    }
+    buf[preamble_len] = MaybeUninit::new(START);
+    preamble_len + 1
+}
+
+fn write_escaped(buf: &mut [MaybeUninit<u8>], idx: usize, byte: u8) -> usize {
+    if needs_escape(byte) {
+        buf[idx] = MaybeUninit::new(ESC);
+        buf[idx + 1] = MaybeUninit::new(byte ^ ESCAPE_XOR);
+        idx + 2
+    } else {
+        buf[idx] = MaybeUninit::new(byte);
+        idx + 1
+    }
+}
+
+// Frame from the start byte to the first stop byte, range is the payload still escaped,
+// see unescape_in_place
+pub(crate) fn parse(config: &BinCrcConfig, capacity: usize, data: &[u8]) -> Parsed {
+    use Parsed::*;
+    if data.is_empty() {
+        return NeedMoreBytes(1);
+    }
+    if data[0] != START {
+        return Invalid(BinCrcError::BadStart);
+    }
+    let crc_len = config.crc_len();
+    let mut state = config.crc_init();
+    // Last crc_len unescaped bytes, at the end of the window, might be the CRC, so they are
+    // fed to the CRC that many bytes late
+    let mut window = [0u8; 4];
+    let mut window_starts = [0usize; 4];
+    let oldest = 4 - crc_len;
+    let mut unescaped = 0;
+    let mut escaped = false;
+    // Only as far as the buffer goes, a stop byte further on doesn't make the frame fit
+    for (i, &b) in data.iter().enumerate().take(capacity).skip(1) {
+        let (value, value_start) = match b {
+            STOP if escaped => { return Invalid(BinCrcError::BadStop); },
+            START => { return Invalid(BinCrcError::BadStart); },
+            STOP => {
+                // Last crc_len of them are the CRC
+                if unescaped < crc_len + config.min_frame_len.max(1) {
+                    return Invalid(BinCrcError::BadLength);
+                }
+                if config.crc_includes_stop {
+                    state = config.crc_update(state, &[STOP]);
+                }
+                let expected_crc = config.crc_finish(state);
+                let received_crc = config.read_crc(&window[oldest..]);
+                let count = i + 1;
+                let range = 1..window_starts[oldest];
+                return if expected_crc == received_crc {
+                    Frame { count, range }
+                } else {
+                    CrcMismatch { count, range, expected_crc, received_crc }
+                };
+            },
+            _ if escaped => {
+                escaped = false;
+                (b ^ ESCAPE_XOR, i - 1)
+            },
+            ESC => {
+                escaped = true;
+                continue;
+            },
+            _ => (b, i)
+        };
+        if unescaped >= crc_len {
+            state = config.crc_update(state, &window[oldest..=oldest]);
+        }
+        window.copy_within(1.., 0);
+        window[3] = value;
+        window_starts.copy_within(1.., 0);
+        window_starts[3] = value_start;
+        unescaped += 1;
+    }
+    // No stop byte yet, frame must still fit into the buffer
+    if data.len() >= capacity {
+        Invalid(BinCrcError::BadLength)
+    } else {
+        NeedMoreBytes(1)
+    }
+}
+
+// Unescapes an escaped payload range from parse, returns where the payload is now
+pub(crate) fn unescape_in_place(buf: &mut [u8], range: Range<usize>) -> Range<usize> {
+    let mut write = range.start;
+    let mut read = range.start;
+    while read < range.end {
+        if buf[read] == ESC {
+            buf[write] = buf[read + 1] ^ ESCAPE_XOR;
+            read += 2;
+        } else {
+            buf[write] = buf[read];
+            read += 1;
+        }
+        write += 1;
+    }
+    range.start..write
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping_round_trip() {
+        let config = BinCrcConfig::default();
+        let payload = [0x01, START, STOP, ESC, 0x20, 0x22];
+        let mut buf = [MaybeUninit::uninit(); 32];
+        let len = commit_uninit(&config, &payload, &mut buf).unwrap();
+        let mut frame: Vec<u8> = buf[..len].iter().map(|b| unsafe { b.assume_init() }).collect();
+        assert_eq!(frame[..10], [START, 0x01, ESC, 0x22, ESC, 0x23, ESC, 0x30, 0x20, 0x22]);
+        assert_eq!(frame.iter().filter(|b| **b == STOP).count(), 1);
+        match parse(&config, 64, &frame) {
+            Parsed::Frame { count, range } => {
+                assert_eq!(count, len);
+                let range = unescape_in_place(&mut frame, range);
+                assert_eq!(frame[range], payload);
+            },
+            _ => panic!("frame expected")
+        }
+    }
+}
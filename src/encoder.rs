@@ -0,0 +1,586 @@
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ops::Range;
+use generic_array::ArrayLength;
+
+#[cfg(feature = "std")]
+use tokio_util::codec::Encoder;
+#[cfg(feature = "std")]
+use bytes::{BytesMut, BufMut};
+
+use crate::{delimited, BinCrcConfig, BinCrcError, Framing, LengthWidth};
+#[cfg(feature = "std")]
+use crate::OversizePayload;
+use crate::config::{LEN8_ONLY, MAX_LEN24};
+
+/// Encoding half of the codec, carries only the configuration and no receive buffer.
+pub struct BinCrcEncoder<N: ArrayLength<u8>> {
+    config: BinCrcConfig,
+    frames_skipped: u64,
+    _n: PhantomData<N>
+}
+
+impl<N: ArrayLength<u8>> Default for BinCrcEncoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: ArrayLength<u8>> BinCrcEncoder<N> {
+    pub fn new() -> Self {
+        Self::with_config(BinCrcConfig::default())
+    }
+
+    pub fn with_config(config: BinCrcConfig) -> Self {
+        BinCrcEncoder { config, frames_skipped: 0, _n: PhantomData }
+    }
+
+    pub fn config(&self) -> &BinCrcConfig {
+        &self.config
+    }
+
+    /// Whether frames this encoder writes are decoded with `other_config`, see
+    /// `BinCrcConfig::same_wire_format`.
+    pub fn config_matches(&self, other_config: &BinCrcConfig) -> bool {
+        self.config.same_wire_format(other_config)
+    }
+
+    /// Payloads `Encoder::encode` skipped for `OversizePayload::Skip`.
+    pub fn frames_skipped(&self) -> u64 {
+        self.frames_skipped
+    }
+
+    /// Number of bytes `commit_frame` needs for a `frame_len` payload with this configuration.
+    pub fn size_hint(&self, frame_len: usize) -> Result<usize, BinCrcError> {
+        size_hint::<N>(&self.config, frame_len)
+    }
+
+    /// Total of `size_hint` over payloads of `frame_lens` bytes, e.g. to reserve a `BytesMut`
+    /// once for a burst of frames `Encoder::encode` writes into it.
+    pub fn size_hint_frames(&self, frame_lens: &[usize]) -> Result<usize, BinCrcError> {
+        size_hint_frames::<N>(&self.config, frame_lens.iter().copied())
+    }
+
+    /// Frames `frame` into `buf` with this configuration, returns the number of bytes written.
+    pub fn commit_frame(&self, frame: &[u8], buf: &mut [u8]) -> Result<usize, BinCrcError> {
+        commit_frame::<N>(&self.config, frame, buf)
+    }
+
+    /// Same as `commit_frame`, with `address` of `BinCrcConfig::address_len` bytes in front of the length field.
+    pub fn commit_frame_addressed(&self, address: &[u8], frame: &[u8], buf: &mut [u8]) -> Result<usize, BinCrcError> {
+        commit_frame_addressed::<N>(&self.config, address, frame, buf)
+    }
+
+    /// Same as `commit_frame`, with `msg_type` as the first payload byte, see `BinCrcDecoder::commit_typed`.
+    pub fn commit_typed(&self, msg_type: u32, payload: &[u8], buf: &mut [u8]) -> Result<usize, BinCrcError> {
+        commit_typed::<N>(&self.config, msg_type, payload, buf)
+    }
+
+    /// Framed bytes of `payloads` with this configuration, see `BinCrcDecoder::encode_iter`.
+    #[cfg(feature = "std")]
+    pub fn encode_iter<I: IntoIterator>(&self, payloads: I) -> EncodeIter<N, I::IntoIter> {
+        EncodeIter::new(self.config, payloads.into_iter())
+    }
+}
+
+// TooBig exactly where committing the frame fails with InvalidLength, but for lengths the fixed
+// payload or a len8-only build can't express at all
+pub(crate) fn size_hint<N: ArrayLength<u8>>(config: &BinCrcConfig, frame_len: usize) -> Result<usize, BinCrcError> {
+    if check_len::<N>(frame_len).is_err() {
+        return Err(BinCrcError::TooBig);
+    }
+    // Exact length depends on how many bytes need escaping
+    if config.framing == Framing::Delimited {
+        return Ok(delimited::max_len(config, frame_len));
+    }
+    let len_value = config.len_value(frame_len)?;
+    match config.start_byte(len_value) {
+        Some(_) => {
+            let header_len = config.header_len(len_value);
+            Ok(config.preamble_len() + header_len + config.address_len + frame_len + config.trailer_len(header_len - 1))
+        },
+        None if LEN8_ONLY || matches!(config.length_width, LengthWidth::FixedPayload(_)) => Err(BinCrcError::InvalidLength),
+        None => Err(BinCrcError::TooBig)
+    }
+}
+
+// Returns the number of bytes written
+pub(crate) fn commit_frame<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    frame: &[u8],
+    buf: &mut[u8]
+) -> Result<usize, BinCrcError>
+{
+    commit_frame_addressed::<N>(config, &[], frame, buf)
+}
+
+pub(crate) fn commit_frame_addressed<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    address: &[u8],
+    frame: &[u8],
+    buf: &mut[u8]
+) -> Result<usize, BinCrcError>
+{
+    // Only initialized values are ever written, so viewing initialized memory as MaybeUninit is fine
+    let buf = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    commit_addressed_uninit::<N>(config, address, frame, buf)
+}
+
+// Bytes a burst of frames takes, for reserving the space once
+pub(crate) fn size_hint_frames<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    frame_lens: impl IntoIterator<Item = usize>
+) -> Result<usize, BinCrcError>
+{
+    frame_lens.into_iter().map(|len| size_hint::<N>(config, len)).sum()
+}
+
+// Frames back to back, each with the length width its own length needs
+pub(crate) fn commit_frames<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    frames: &[&[u8]],
+    buf: &mut [u8]
+) -> Result<usize, BinCrcError>
+{
+    let mut written = 0;
+    for (idx, frame) in frames.iter().enumerate() {
+        written += match commit_frame::<N>(config, frame, &mut buf[written..]) {
+            Err(BinCrcError::NotEnoughSpace { .. }) => {
+                let rest = size_hint_frames::<N>(config, frames[idx..].iter().map(|f| f.len()))?;
+                return Err(BinCrcError::NotEnoughSpace { required: written + rest, available: buf.len() });
+            },
+            result => result?
+        };
+    }
+    Ok(written)
+}
+
+// Frames msg_type followed by payload, both go through buf first so that nothing is copied twice
+pub(crate) fn commit_typed<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    msg_type: u32,
+    payload: &[u8],
+    buf: &mut [u8]
+) -> Result<usize, BinCrcError>
+{
+    let type_len = config.type_width.bytes();
+    let frame_len = payload.len() + type_len;
+    let available = buf.len();
+    let staged = match buf.get_mut(..frame_len) {
+        Some(staged) => staged,
+        None => { return Err(BinCrcError::NotEnoughSpace { required: size_hint::<N>(config, frame_len)?, available }); }
+    };
+    config.type_byte_order.write(msg_type, &mut staged[..type_len]).ok_or(BinCrcError::InvalidLength)?;
+    staged[type_len..].copy_from_slice(payload);
+    commit_in_place::<N>(config, buf, 0..frame_len)
+}
+
+// Initializes exactly the bytes of the frame and returns their count
+pub(crate) fn commit_frame_uninit<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    frame: &[u8],
+    buf: &mut [MaybeUninit<u8>]
+) -> Result<usize, BinCrcError>
+{
+    commit_addressed_uninit::<N>(config, &[], frame, buf)
+}
+
+fn commit_addressed_uninit<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    address: &[u8],
+    frame: &[u8],
+    buf: &mut [MaybeUninit<u8>]
+) -> Result<usize, BinCrcError>
+{
+    if config.framing == Framing::Delimited {
+        check_len::<N>(frame.len())?;
+        return delimited::commit_uninit(config, frame, buf);
+    }
+    let (first_byte, header_len) = layout::<N>(config, address, frame.len(), buf.len())?;
+    let data_start_idx = config.preamble_len() + header_len + config.crc_ahead_len();
+    write(&mut buf[data_start_idx .. data_start_idx + frame.len()], frame);
+    Ok(commit_around(config, first_byte, address, header_len, frame.len(), checks(config, frame), buf))
+}
+
+// Payload is already in buf at payload, it is moved to where the frame needs it
+pub(crate) fn commit_in_place<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    buf: &mut [u8],
+    payload: Range<usize>
+) -> Result<usize, BinCrcError>
+{
+    if payload.start > payload.end || payload.end > buf.len() {
+        return Err(BinCrcError::InvalidLength);
+    }
+    let frame_len = payload.len();
+    if config.framing == Framing::Delimited {
+        check_len::<N>(frame_len)?;
+        return delimited::commit_in_place(config, buf, payload);
+    }
+    let (first_byte, header_len) = layout::<N>(config, &[], frame_len, buf.len())?;
+    let data_start_idx = config.preamble_len() + header_len + config.crc_ahead_len();
+    buf.copy_within(payload, data_start_idx);
+    let checks = checks(config, &buf[data_start_idx .. data_start_idx + frame_len]);
+    // Same as in commit_frame, only initialized values are written
+    let buf = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+    Ok(commit_around(config, first_byte, &[], header_len, frame_len, checks, buf))
+}
+
+fn check_len<N: ArrayLength<u8>>(frame_len: usize) -> Result<(), BinCrcError> {
+    if frame_len <= N::to_usize() || frame_len <= 255 {
+        Ok(())
+    } else {
+        Err(BinCrcError::InvalidLength)
+    }
+}
+
+// Start byte and header length with the address for a frame_len payload, if the frame fits
+// into buf_len bytes
+fn layout<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    address: &[u8],
+    frame_len: usize,
+    buf_len: usize
+) -> Result<(u8, usize), BinCrcError>
+{
+    check_len::<N>(frame_len)?;
+    if address.len() != config.address_len {
+        return Err(BinCrcError::InvalidLength);
+    }
+    let len_value = config.len_value(frame_len)?;
+    let first_byte = config.start_byte(len_value).ok_or(BinCrcError::InvalidLength)?;
+    let len_field = config.header_len(len_value) - 1;
+    let header_len = 1 + address.len() + len_field;
+    // Saturating, N may be close to a 16 bit usize limit
+    let bytes_required = frame_len.saturating_add(config.preamble_len() + header_len + config.trailer_len(len_field));
+    if buf_len < bytes_required {
+        return Err(BinCrcError::NotEnoughSpace { required: bytes_required, available: buf_len });
+    }
+    Ok((first_byte, header_len))
+}
+
+// Writes everything but the payload, which must already be in place, returns the frame length
+fn commit_around(
+    config: &BinCrcConfig,
+    first_byte: u8,
+    address: &[u8],
+    header_len: usize,
+    frame_len: usize,
+    (crc, checksum): (u32, Option<u8>),
+    buf: &mut [MaybeUninit<u8>]
+) -> usize
+{
+    let (head, buf) = buf.split_at_mut(config.preamble_len());
+    if let Some(preamble) = config.preamble {
+        head.iter_mut().for_each(|b| *b = MaybeUninit::new(preamble.byte));
+    }
+    buf[0] = MaybeUninit::new(first_byte);
+    write(&mut buf[1..], address);
+    let len_start = 1 + address.len();
+    // Checked by layout already
+    let len_value = frame_len / config.len_unit();
+    debug_assert!(len_value as u64 <= MAX_LEN24);
+    match config.length_width {
+        LengthWidth::Varint => {
+            let mut len = len_value;
+            for b in &mut buf[len_start..header_len] {
+                let more = if len >= 0x80 { 0x80 } else { 0 };
+                *b = MaybeUninit::new((len & 0x7f) as u8 | more);
+                len >>= 7;
+            }
+        },
+        LengthWidth::FixedPayload(_) => {},
+        _ if first_byte == 2 || LEN8_ONLY => {
+            buf[len_start] = MaybeUninit::new(len_value as u8);
+        },
+        _ if first_byte == 3 => {
+            write(&mut buf[len_start..len_start + 2], &(len_value as u16).to_be_bytes());
+        },
+        _ => {
+            write(&mut buf[len_start..len_start + 3], &(len_value as u32).to_be_bytes()[1..]);
+        }
+    }
+    let crc_start_idx = if config.crc_ahead_len() == 0 { header_len + frame_len } else { header_len };
+    let crc_len = config.crc_len();
+    write(&mut buf[crc_start_idx .. crc_start_idx + crc_len], &crc.to_be_bytes()[4 - crc_len..]);
+    // Payload and CRC in either order
+    let mut stop_idx = header_len + frame_len + crc_len;
+    if let Some(checksum) = checksum {
+        buf[stop_idx] = MaybeUninit::new(checksum);
+        stop_idx += 1;
+    }
+    if config.trailing_length_check {
+        buf.copy_within(len_start..header_len, stop_idx);
+        stop_idx += header_len - len_start;
+    }
+    if config.omit_stop_byte {
+        return head.len() + stop_idx;
+    }
+    buf[stop_idx] = MaybeUninit::new(3);
+    head.len() + stop_idx + 1
+}
+
+// CRC and the optional checksum of the payload
+fn checks(config: &BinCrcConfig, payload: &[u8]) -> (u32, Option<u8>) {
+    (config.crc(payload), config.checksum.map(|c| c.calculate(payload)))
+}
+
+fn write(dst: &mut [MaybeUninit<u8>], src: &[u8]) {
+    dst.iter_mut().zip(src).for_each(|(d, s)| *d = MaybeUninit::new(*s));
+}
+
+// Whether OversizePayload::Skip leaves the payload out, a len8-only build can't frame more than
+// 255 bytes at all
+#[cfg(feature = "std")]
+fn skips(config: &BinCrcConfig, error: &BinCrcError, frame_len: usize) -> bool {
+    config.oversize_payload == OversizePayload::Skip && match error {
+        BinCrcError::TooBig => true,
+        BinCrcError::InvalidLength => LEN8_ONLY && frame_len > 255,
+        _ => false
+    }
+}
+
+// Returns whether the frame was written, too long payloads are skipped if the config says so
+#[cfg(feature = "std")]
+pub(crate) fn encode<N: ArrayLength<u8>>(
+    config: &BinCrcConfig,
+    item: &[u8],
+    buf: &mut BytesMut
+) -> Result<bool, BinCrcError>
+{
+    let size_hint = match size_hint::<N>(config, item.len()) {
+        Err(e) if skips(config, &e, item.len()) => { return Ok(false); },
+        size_hint => size_hint?
+    };
+    buf.reserve(size_hint);
+    // Written straight into spare capacity, without zeroing it first
+    let written = commit_frame_uninit::<N>(config, item, &mut buf.bytes_mut()[..size_hint])?;
+    unsafe {
+        buf.advance_mut(written);
+    }
+    Ok(true)
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>> Encoder for BinCrcEncoder<N> {
+    type Item = Vec<u8>;
+    type Error = BinCrcError;
+
+    fn encode(&mut self, item: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
+        if !encode::<N>(&self.config, item.as_slice(), buf)? {
+            self.frames_skipped += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Framed bytes of a sequence of payloads, each framed only once its bytes are needed,
+/// see `BinCrcDecoder::encode_iter`.
+///
+/// Ends at the first payload that can't be framed, `error` tells which error that was.
+/// With `OversizePayload::Skip` payloads too long to be framed are left out instead.
+#[cfg(feature = "std")]
+pub struct EncodeIter<N: ArrayLength<u8>, I> {
+    config: BinCrcConfig,
+    payloads: I,
+    // Frame being handed out, reused for every payload
+    frame: Vec<u8>,
+    pos: usize,
+    error: Option<BinCrcError>,
+    _n: PhantomData<N>
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>, I> EncodeIter<N, I> {
+    pub(crate) fn new(config: BinCrcConfig, payloads: I) -> Self {
+        EncodeIter { config, payloads, frame: Vec::new(), pos: 0, error: None, _n: PhantomData }
+    }
+
+    /// Why the output ended early, `None` while all payloads were framed.
+    pub fn error(&self) -> Option<&BinCrcError> {
+        self.error.as_ref()
+    }
+
+    fn load(&mut self, payload: &[u8]) -> Result<(), BinCrcError> {
+        self.frame.resize(size_hint::<N>(&self.config, payload.len())?, 0);
+        let written = commit_frame::<N>(&self.config, payload, &mut self.frame)?;
+        self.frame.truncate(written);
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<N: ArrayLength<u8>, I> Iterator for EncodeIter<N, I>
+    where I: Iterator, I::Item: AsRef<[u8]>
+{
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        while self.pos == self.frame.len() {
+            if self.error.is_some() {
+                return None;
+            }
+            let payload = self.payloads.next()?;
+            let payload = payload.as_ref();
+            match self.load(payload) {
+                Ok(()) => {},
+                Err(e) if skips(&self.config, &e, payload.len()) => {},
+                Err(e) => {
+                    self.frame.clear();
+                    self.pos = 0;
+                    self.error = Some(e);
+                }
+            }
+        }
+        self.pos += 1;
+        Some(self.frame[self.pos - 1])
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::U64;
+
+    #[test]
+    fn same_output_as_bincrc() {
+        let mut via_encoder = BytesMut::new();
+        BinCrcEncoder::<U64>::new().encode(vec![1, 2, 3], &mut via_encoder).unwrap();
+        let mut via_bincrc = BytesMut::new();
+        BinCrc::<U64>::new().encode(vec![1, 2, 3], &mut via_bincrc).unwrap();
+        assert_eq!(via_encoder, via_bincrc);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn configured_encoder() {
+        let config = BinCrcConfig { length_width: LengthWidth::U16, trailing_length_check: true, ..Default::default() };
+        let encoder = BinCrcEncoder::<U64>::with_config(config);
+        let mut buf = [0; 32];
+        let written = encoder.commit_frame(&[1, 2, 3], &mut buf).unwrap();
+        assert_eq!(written, encoder.size_hint(3).unwrap());
+        assert_eq!(BinCrc::<U64>::verify_frame_with(&config, &buf[..written]).unwrap(), 3..6);
+        assert!(matches!(encoder.commit_frame(&[0; 30], &mut buf), Err(BinCrcError::NotEnoughSpace { .. })));
+    }
+
+    #[test]
+    fn skip_oversize_payload() {
+        let mut buf = BytesMut::new();
+        let mut encoder = BinCrcEncoder::<U64>::new();
+        assert!(matches!(encoder.encode(vec![0; 300], &mut buf), Err(BinCrcError::TooBig)));
+
+        let config = BinCrcConfig { oversize_payload: OversizePayload::Skip, ..Default::default() };
+        let mut encoder = BinCrcEncoder::<U64>::with_config(config);
+        encoder.encode(vec![1], &mut buf).unwrap();
+        encoder.encode(vec![0; 300], &mut buf).unwrap();
+        encoder.encode(vec![2], &mut buf).unwrap();
+        assert_eq!(encoder.frames_skipped(), 1);
+        assert_eq!(&buf[..], &[BinCrc::<U64>::frame([1]).unwrap(), BinCrc::<U64>::frame([2]).unwrap()].concat()[..]);
+
+        // Other errors are still errors
+        let config = BinCrcConfig { length_unit: crate::LengthUnit::Words16, ..config };
+        let mut bincrc = BinCrc::<U64>::with_config(config);
+        assert!(matches!(bincrc.encode(vec![1], &mut buf), Err(BinCrcError::OddLength)));
+        bincrc.encode(vec![0; 300], &mut buf).unwrap();
+        assert_eq!(bincrc.stats().frames_skipped, 1);
+    }
+
+    #[test]
+    fn one_byte_short() {
+        let config = BinCrcConfig::default();
+        let required = size_hint::<U64>(&config, 10).unwrap();
+        let mut buf = vec![0; required - 1];
+        match commit_frame::<U64>(&config, &[7; 10], &mut buf) {
+            Err(BinCrcError::NotEnoughSpace { required: r, available }) => assert_eq!((r, available), (required, required - 1)),
+            other => panic!("{:?}", other)
+        }
+        match commit_typed::<U64>(&config, 1, &[7; 10], &mut buf[..5]) {
+            Err(BinCrcError::NotEnoughSpace { required: r, available }) => assert_eq!((r, available), (required + 1, 5)),
+            other => panic!("{:?}", other)
+        }
+        // Batches report what the whole batch needs, so the buffer can be grown once
+        let mut buf = vec![0; 3 * required - 1];
+        match commit_frames::<U64>(&config, &[&[7; 10], &[7; 10], &[7; 10]], &mut buf) {
+            Err(BinCrcError::NotEnoughSpace { required: r, available }) => assert_eq!((r, available), (3 * required, buf.len())),
+            other => panic!("{:?}", other)
+        }
+        buf.push(0);
+        assert_eq!(commit_frames::<U64>(&config, &[&[7; 10], &[7; 10], &[7; 10]], &mut buf).unwrap(), 3 * required);
+    }
+
+    #[test]
+    fn reserved_burst() {
+        let lens: Vec<usize> = (1..=200).map(|i| i % 60 + 1).collect();
+        let mut encoder = BinCrcEncoder::<U64>::new();
+        let total = encoder.size_hint_frames(&lens).unwrap();
+        assert_eq!(total, BinCrc::<U64>::size_hint_frames(&lens).unwrap());
+        let mut buf = BytesMut::new();
+        buf.reserve(total);
+        let storage = buf.as_ptr();
+        for len in &lens {
+            encoder.encode(vec![0x55; *len], &mut buf).unwrap();
+        }
+        // Nothing was reallocated on the way
+        assert_eq!(buf.as_ptr(), storage);
+        assert_eq!(buf.len(), total);
+        assert!(matches!(encoder.size_hint_frames(&[1, 300]), Err(BinCrcError::TooBig)));
+
+        // Single frames reserve what they need themselves
+        let mut buf = BytesMut::new();
+        encoder.encode(vec![1; 60], &mut buf).unwrap();
+        assert_eq!(buf.len(), encoder.size_hint(60).unwrap());
+        assert!(buf.capacity() >= buf.len());
+    }
+
+    #[test]
+    fn encode_iter() {
+        let payloads = vec![vec![1; 10], vec![2; 300], vec![3; 5]];
+        let mut bytes = BinCrc::<U64>::encode_iter(payloads.clone());
+        assert_eq!(bytes.by_ref().collect::<Vec<u8>>(), BinCrc::<U64>::frame([1; 10]).unwrap());
+        assert!(matches!(bytes.error(), Some(BinCrcError::TooBig)));
+
+        let config = BinCrcConfig { oversize_payload: OversizePayload::Skip, ..Default::default() };
+        let bytes = BinCrcEncoder::<U64>::with_config(config).encode_iter(payloads);
+        let expected = [BinCrc::<U64>::frame([1; 10]).unwrap(), BinCrc::<U64>::frame([3; 5]).unwrap()].concat();
+        assert_eq!(bytes.collect::<Vec<u8>>(), expected);
+    }
+
+    #[cfg(not(feature = "len8-only"))]
+    #[test]
+    fn commit_into_uninit() {
+        use crate::typenum::U512;
+
+        let config = BinCrcConfig { trailing_length_check: true, ..Default::default() };
+        let mut expected = vec![0; size_hint::<U512>(&config, 300).unwrap()];
+        commit_frame::<U512>(&config, &[0x55; 300], &mut expected).unwrap();
+        let mut buf = [MaybeUninit::<u8>::uninit(); 400];
+        let written = commit_frame_uninit::<U512>(&config, &[0x55; 300], &mut buf).unwrap();
+        assert_eq!(written, expected.len());
+        let written: Vec<u8> = buf[..written].iter().map(|b| unsafe { b.assume_init() }).collect();
+        assert_eq!(written, expected);
+        assert!(matches!(
+            commit_frame_uninit::<U512>(&config, &[0x55; 300], &mut buf[..300]),
+            Err(BinCrcError::NotEnoughSpace { .. })
+        ));
+    }
+
+    #[test]
+    fn commit_in_place_overlapping() {
+        let config = BinCrcConfig { preamble: Some(crate::Preamble { byte: 0x55, len: 2 }), ..Default::default() };
+        let payload: Vec<u8> = (1..=20).collect();
+        let mut expected = vec![0; size_hint::<U64>(&config, payload.len()).unwrap()];
+        commit_frame::<U64>(&config, &payload, &mut expected).unwrap();
+        // Payload ahead of, overlapping and behind where the frame needs it
+        for &offset in &[0, 3, 4, 7] {
+            let mut buf = vec![0xAA; 40];
+            buf[offset..offset + payload.len()].copy_from_slice(&payload);
+            let written = commit_in_place::<U64>(&config, &mut buf, offset..offset + payload.len()).unwrap();
+            assert_eq!(&buf[..written], &expected[..]);
+        }
+        let mut buf = vec![0; 24];
+        assert!(matches!(commit_in_place::<U64>(&config, &mut buf, 0..20), Err(BinCrcError::NotEnoughSpace { .. })));
+        assert!(matches!(commit_in_place::<U64>(&config, &mut buf, 10..30), Err(BinCrcError::InvalidLength)));
+    }
+}
@@ -0,0 +1,304 @@
+use core::convert::TryFrom;
+use core::ops::Range;
+
+use crate::{delimited, BinCrcConfig, BinCrcError, Framing, LenField, LengthWidth};
+use crate::config::{LEN8_ONLY, MAX_VARINT_LEN};
+
+// Outcome of parsing a frame at the beginning of a byte slice
+pub(crate) enum Parsed {
+    // At least this many more bytes are needed to make progress
+    NeedMoreBytes(usize),
+    Invalid(BinCrcError),
+    // Header is fine, but a frame with claimed_len bytes of payload doesn't fit into the buffer
+    TooBig { claimed_len: usize },
+    // Frame of count bytes with payload at range
+    Frame { count: usize, range: Range<usize> },
+    // Framing is fine, but CRC is not
+    CrcMismatch { count: usize, range: Range<usize>, expected_crc: u32, received_crc: u32 }
+}
+
+// Stateless validation shared by the streaming decoder and verify_frame,
+// capacity is the size of the buffer the whole frame must fit into.
+pub(crate) fn parse<L: LenField>(config: &BinCrcConfig, capacity: usize, data: &[u8]) -> Parsed {
+    parse_resuming::<L>(config, capacity, data, None)
+}
+
+// Same as parse, crc_prefix is the CRC state over that many leading payload bytes, taken
+// as is rather than computed again
+pub(crate) fn parse_resuming<L: LenField>(
+    config: &BinCrcConfig,
+    capacity: usize,
+    data: &[u8],
+    crc_prefix: Option<(usize, u32)>
+) -> Parsed
+{
+    use Parsed::*;
+    if config.framing == Framing::Delimited {
+        return delimited::parse(config, capacity, data);
+    }
+    // Constant unless L is DynLen, so that only the branches for one width remain
+    let width = L::width(config);
+    // Need at least 1 byte
+    if data.is_empty() {
+        //rprintln!("T2");
+        return NeedMoreBytes(1);
+    }
+    // Check start byte
+    let b0 = data[0];
+    let is_len_8b = b0 == 2;
+    let is_len_16b = b0 == 3;
+    let is_len_24b = b0 == 4;
+    if !is_len_8b && !is_len_16b && !is_len_24b {
+        //rprintln!("T3");
+        return Invalid(BinCrcError::BadStart);
+    }
+    // Only one of the widths is used when it's fixed
+    match width {
+        LengthWidth::Auto => {},
+        LengthWidth::U8 if is_len_8b => {},
+        LengthWidth::U16 if is_len_16b => {},
+        LengthWidth::Varint | LengthWidth::FixedPayload(_) if is_len_8b => {},
+        _ => { return Invalid(BinCrcError::BadStart); }
+    }
+    // Ignore too big frames right away, including any 24 bit one in a buffer too small for them
+    let max_len24 = capacity.saturating_sub(4 + config.address_len + config.trailer_len(3));
+    if (LEN8_ONLY && !is_len_8b) || (is_len_24b && max_len24 <= u16::MAX as usize) {
+        //rprintln!("T4");
+        return Invalid(BinCrcError::BadLength);
+    }
+    // Address bytes come before the length field
+    let address_len = config.address_len;
+    if data.len() <= address_len {
+        return NeedMoreBytes(address_len + 1 - data.len());
+    }
+    let (len_field, frame_len) = match width {
+        // Length of the length is not known in advance, ask for one byte at a time
+        LengthWidth::Varint => match read_varint(&data[1 + address_len..]) {
+            Varint::Incomplete => { return NeedMoreBytes(1); },
+            Varint::Invalid => { return Invalid(BinCrcError::BadLength); },
+            Varint::Value(len, _) if LEN8_ONLY && len > 255 => { return Invalid(BinCrcError::BadLength); },
+            Varint::Value(len, len_field) => (len_field, len.saturating_mul(config.len_unit()))
+        },
+        LengthWidth::FixedPayload(len) => (0, len as usize * config.len_unit()),
+        _ => match fixed_len(width, b0, &data[address_len..]) {
+            Ok(len) => (b0 as usize - 1, len.saturating_mul(config.len_unit())),
+            Err(parsed) => { return parsed; }
+        }
+    };
+    let header_len = 1 + address_len + len_field;
+    //rprintln!("frame_len: {}", frame_len);
+    if frame_len < config.min_frame_len {
+        return Invalid(BinCrcError::BadLength);
+    }
+    // Ignore too big frames, whole frame with the header and trailer must fit into the buffer,
+    // so that all the indexing below stays in bounds. Compared without adding to frame_len first,
+    // a 16 bit length plus overhead doesn't fit into a 16 bit usize.
+    let overhead = header_len + config.trailer_len(len_field);
+    if frame_len > capacity.saturating_sub(overhead) {
+        //rprintln!("T8");
+        return TooBig { claimed_len: frame_len };
+    }
+    // Can't overflow after the check above, but a wrapped count would index out of bounds
+    let count = match frame_len.checked_add(overhead) {
+        Some(count) => count,
+        None => { return Invalid(BinCrcError::BadLength); }
+    };
+    debug_assert!(count <= capacity);
+    // Rest of the frame
+    if data.len() < count {
+        //rprintln!("T9");
+        return NeedMoreBytes(count - data.len());
+    }
+    let layout = FrameLayout { header_len, len_field, frame_len };
+    check_frame(config, &data[..count], layout, crc_prefix).unwrap_or_else(|invalid| invalid)
+}
+
+// Where things are in a frame whose length is known
+#[derive(Clone, Copy)]
+struct FrameLayout {
+    header_len: usize,
+    len_field: usize,
+    frame_len: usize
+}
+
+// Stop byte, length copy and CRC of a whole frame. Every field is looked up within the frame,
+// so that a layout the checks before got wrong makes a bad frame rather than a panic.
+fn check_frame(
+    config: &BinCrcConfig,
+    frame: &[u8],
+    layout: FrameLayout,
+    crc_prefix: Option<(usize, u32)>
+) -> Result<Parsed, Parsed>
+{
+    use Parsed::*;
+    let FrameLayout { header_len, len_field, frame_len } = layout;
+    let field = |start: usize, len: usize| {
+        start.checked_add(len).and_then(|end| frame.get(start..end)).ok_or(Invalid(BinCrcError::BadLength))
+    };
+    let count = frame.len();
+    // Invalid stop byte
+    let stop_idx = count.checked_sub(config.stop_len()).ok_or(Invalid(BinCrcError::BadLength))?;
+    if !config.omit_stop_byte && field(stop_idx, 1)? != [3] {
+        //rprintln!("T10");
+        return Err(Invalid(BinCrcError::BadStop));
+    }
+    // Length copy in front of the stop byte must match the one in the header
+    if config.trailing_length_check {
+        let copy_idx = stop_idx.checked_sub(len_field).ok_or(Invalid(BinCrcError::BadLength))?;
+        if field(copy_idx, len_field)? != field(header_len - len_field, len_field)? {
+            return Err(Invalid(BinCrcError::BadLength));
+        }
+    }
+    // Check CRC
+    let payload_start = header_len + config.crc_ahead_len();
+    let crc_idx = if config.crc_ahead_len() == 0 { header_len + frame_len } else { header_len };
+    let received_crc = config.read_crc(field(crc_idx, config.crc_len())?);
+    let payload = field(payload_start, frame_len)?;
+    let range = Range{
+        start: payload_start,
+        end: payload_start + frame_len
+    };
+    let crc = match crc_prefix {
+        Some((covered, state)) if covered <= frame_len => config.crc_resume(state, &payload[covered..]),
+        _ => config.crc(payload)
+    };
+    if crc == received_crc {
+        //rprintln!("vesc_valid");
+        if !config.checksum_ok(payload, frame.get(header_len + frame_len + config.crc_len()).copied()) {
+            return Err(Invalid(BinCrcError::ChecksumMismatch));
+        }
+        Ok(Frame { count, range })
+    } else {
+        //rprintln!("crc r:{:04x} c:{:04x}", received_crc, crc);
+        Ok(CrcMismatch { count, range, expected_crc: crc, received_crc })
+    }
+}
+
+// 8, 16 or 24 bit length field after start byte b0, data[0] is the byte before the length field
+fn fixed_len(width: LengthWidth, b0: u8, data: &[u8]) -> Result<usize, Parsed> {
+    use Parsed::*;
+    // Not enough bytes to determine length
+    if data.len() < b0 as usize {
+        //rprintln!("T5");
+        return Err(NeedMoreBytes(b0 as usize - data.len()));
+    }
+    let len = data[1 .. b0 as usize].iter().fold(0u32, |len, b| len << 8 | *b as u32);
+    // Lengths a narrower field can carry are only taken from it, unless the width is fixed
+    let min_len = match b0 {
+        2 => 1,
+        3 if width == LengthWidth::U16 => 1,
+        3 => u8::MAX as u32 + 1,
+        _ => u16::MAX as u32 + 1
+    };
+    if len < min_len {
+        //rprintln!("T6");
+        return Err(Invalid(BinCrcError::BadLength));
+    }
+    // Can't fit into a 16 bit usize, too big for the buffer anyway
+    Ok(usize::try_from(len).unwrap_or(usize::MAX))
+}
+
+pub(crate) enum Varint {
+    // Data ended before the last byte
+    Incomplete,
+    Invalid,
+    // Value and the number of bytes it took
+    Value(usize, usize)
+}
+
+// LEB128 length of at most MAX_VARINT_LEN bytes, nonzero and fitting into 16 bits
+pub(crate) fn read_varint(data: &[u8]) -> Varint {
+    let mut value = 0;
+    for (i, b) in data.iter().take(MAX_VARINT_LEN).enumerate() {
+        value |= ((b & 0x7f) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            // Overlong encodings are rejected, so that every length has only one representation
+            if (i != 0 && *b == 0) || value == 0 || value > u16::MAX as usize {
+                return Varint::Invalid;
+            }
+            return Varint::Value(value, i + 1);
+        }
+    }
+    if data.len() >= MAX_VARINT_LEN {
+        Varint::Invalid
+    } else {
+        Varint::Incomplete
+    }
+}
+
+pub(crate) enum Anchored {
+    NoMatch,
+    CrcMismatch,
+    // Payload range within the window
+    Frame(Range<usize>)
+}
+
+// Checks whether the whole window is a frame with a header of header_len bytes, not counting
+// the address, by its length field and CRC alone, start and stop byte values are not looked at
+pub(crate) fn parse_anchored(config: &BinCrcConfig, window: &[u8], header_len: usize) -> Anchored {
+    let len_field = header_len - 1;
+    let len_start = 1 + config.address_len;
+    let b0 = len_start + len_field;
+    let trailer = config.trailer_len(len_field);
+    if window.len() < b0 + 1 + trailer {
+        return Anchored::NoMatch;
+    }
+    let frame_len = window.len() - b0 - trailer;
+    let claimed = if config.length_width == LengthWidth::Varint {
+        match read_varint(&window[len_start..b0]) {
+            Varint::Value(len, bytes) if bytes == len_field => len,
+            _ => { return Anchored::NoMatch; }
+        }
+    } else if let LengthWidth::FixedPayload(len) = config.length_width {
+        len as usize
+    } else {
+        let len = window[len_start..b0].iter().fold(0u32, |len, b| len << 8 | *b as u32);
+        usize::try_from(len).unwrap_or(usize::MAX)
+    };
+    let claimed_len = claimed.saturating_mul(config.len_unit());
+    let min_claimed = match header_len {
+        3 if config.length_width == LengthWidth::Auto => u8::MAX as usize + 1,
+        4 if config.length_width == LengthWidth::Auto => u16::MAX as usize + 1,
+        _ => 1
+    };
+    let min_len = config.len_unit().saturating_mul(min_claimed).max(config.min_frame_len);
+    if claimed_len != frame_len || frame_len < min_len {
+        return Anchored::NoMatch;
+    }
+    let stop_idx = window.len() - config.stop_len();
+    if config.trailing_length_check && window[stop_idx - len_field .. stop_idx] != window[len_start..b0] {
+        return Anchored::NoMatch;
+    }
+    let payload = b0 + config.crc_ahead_len() .. b0 + config.crc_ahead_len() + frame_len;
+    let crc_idx = if config.crc_ahead_len() == 0 { payload.end } else { b0 };
+    let received_crc = config.read_crc(&window[crc_idx..]);
+    if config.crc(&window[payload.clone()]) == received_crc {
+        if !config.checksum_ok(&window[payload.clone()], window.get(b0 + frame_len + config.crc_len()).copied()) {
+            return Anchored::NoMatch;
+        }
+        Anchored::Frame(payload)
+    } else {
+        Anchored::CrcMismatch
+    }
+}
+
+// Whole buf must be exactly one valid frame
+pub(crate) fn verify<L: LenField>(config: &BinCrcConfig, capacity: usize, buf: &[u8]) -> Result<Range<usize>, BinCrcError> {
+    match parse::<L>(config, capacity, buf) {
+        Parsed::NeedMoreBytes(_) => Err(BinCrcError::BadLength),
+        Parsed::Invalid(e) => Err(e),
+        Parsed::TooBig { .. } => Err(BinCrcError::TooBig),
+        Parsed::Frame { count, range } if count == buf.len() => Ok(range),
+        Parsed::Frame { .. } => Err(BinCrcError::BadLength),
+        Parsed::CrcMismatch { .. } => Err(BinCrcError::CrcMismatch)
+    }
+}
+
+// Same checks as verify, but integrity failures are Ok(false) rather than errors
+pub(crate) fn check_crc<L: LenField>(config: &BinCrcConfig, capacity: usize, buf: &[u8]) -> Result<bool, BinCrcError> {
+    match verify::<L>(config, capacity, buf) {
+        Ok(_) => Ok(true),
+        Err(BinCrcError::CrcMismatch) | Err(BinCrcError::ChecksumMismatch) => Ok(false),
+        Err(e) => Err(e)
+    }
+}
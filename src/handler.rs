@@ -0,0 +1,24 @@
+use core::ops::Range;
+
+/// Maximum number of handlers a decoder can have registered at once.
+pub const MAX_HANDLERS: usize = 4;
+
+/// Custom frame format, selected by the start byte it was registered for.
+///
+/// Handlers take precedence over the built-in formats, so registering start byte 2, 3 or 4
+/// replaces the corresponding built-in one.
+pub trait FrameHandler: Sync {
+    /// `data` holds all the buffered bytes, starting with the start byte.
+    fn decode_frame(&self, data: &[u8]) -> HandlerResult;
+}
+
+/// Outcome of `FrameHandler::decode_frame`, offsets are relative to `data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandlerResult {
+    /// At least this many more bytes are needed before the next attempt
+    NeedMoreBytes(usize),
+    /// Not a frame, decoder moves on to the next byte
+    InvalidData,
+    /// Frame of `count` bytes with the payload at `range`
+    Consumed(usize, Range<usize>)
+}
@@ -0,0 +1,49 @@
+use crate::{BinCrcConfig, LengthWidth};
+
+/// Length field width as a decoder type parameter, so that decoding is compiled for one width only.
+///
+/// The fixed types override `BinCrcConfig::length_width`, `DynLen` uses it as is. There is no
+/// fixed 24 bit type because there is no 24 bit only `LengthWidth`: 24 bit lengths are only
+/// reachable through `AutoLen`, or `DynLen` with `LengthWidth::Auto`, for payloads over 65535 bytes.
+pub trait LenField {
+    /// `None` takes the width from the config at run time.
+    const WIDTH: Option<LengthWidth>;
+
+    fn width(config: &BinCrcConfig) -> LengthWidth {
+        Self::WIDTH.unwrap_or(config.length_width)
+    }
+
+    // Config with the width this type dictates
+    #[doc(hidden)]
+    fn config(config: &BinCrcConfig) -> BinCrcConfig {
+        BinCrcConfig { length_width: Self::width(config), ..*config }
+    }
+}
+
+/// Width from `BinCrcConfig::length_width`, the default.
+pub struct DynLen;
+
+/// 8, 16 or 24 bit depending on the payload length, same as `LengthWidth::Auto`.
+pub struct AutoLen;
+
+/// 8 bit only, same as `LengthWidth::U8`.
+pub struct U8Len;
+
+/// 16 bit only, same as `LengthWidth::U16`.
+pub struct U16Len;
+
+impl LenField for DynLen {
+    const WIDTH: Option<LengthWidth> = None;
+}
+
+impl LenField for AutoLen {
+    const WIDTH: Option<LengthWidth> = Some(LengthWidth::Auto);
+}
+
+impl LenField for U8Len {
+    const WIDTH: Option<LengthWidth> = Some(LengthWidth::U8);
+}
+
+impl LenField for U16Len {
+    const WIDTH: Option<LengthWidth> = Some(LengthWidth::U16);
+}
@@ -1,231 +1,98 @@
 #![cfg_attr(not(feature = "std"), no_std)]
-use generic_array::{GenericArray, ArrayLength};
-use core::convert::TryInto;
-use crc16;
-use core::ops::Range;
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
+#[cfg(feature = "allocator-api")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod buffered;
+mod chunk;
+mod compare;
+mod config;
+mod crc;
+mod decoder;
+mod delimited;
+mod encoder;
+mod frame;
+mod handler;
+mod len;
+#[cfg(feature = "tokio-serial")]
+mod link;
+mod observer;
+mod queue;
 #[cfg(feature = "std")]
-use tokio_util::codec::{Encoder, Decoder};
+mod ring;
+#[cfg(feature = "test-support")]
+mod selftest;
+mod slice;
+mod stats;
 #[cfg(feature = "std")]
-use bytes::{BytesMut, BufMut};
+mod stream;
 
+#[cfg(feature = "std")]
+pub use buffered::BufferedDecoder;
+#[cfg(feature = "std")]
+pub use chunk::Reassembler;
+pub use compare::{frame_hash, frames_equal, frames_equal_ct};
+pub use crc::{Crc16Backend, CrcBackend, SoftwareCrc, Sum16, TableCrc};
+pub use config::{BinCrcConfig, ByteOrder, Checksum8, CrcPosition, CrcWidth, Framing, LengthUnit, LengthWidth, OversizePayload, OversizeRecovery, Preamble, Resync, TypeWidth};
+pub use decoder::{BinCrcDecoder, BytesNeeded, SuspectFrame};
+#[cfg(feature = "std")]
+pub use decoder::{OffsetDecoder, StreamFrame};
+#[cfg(feature = "test-support")]
+pub use decoder::DecodeResult;
+pub use encoder::BinCrcEncoder;
+#[cfg(feature = "std")]
+pub use encoder::EncodeIter;
+pub use handler::{FrameHandler, HandlerResult, MAX_HANDLERS};
+pub use len::{AutoLen, DynLen, LenField, U16Len, U8Len};
+#[cfg(feature = "tokio-serial")]
+pub use link::{Frames, SerialLink};
+pub use observer::{DecoderEvent, SyncObserver, CRC_FAILURE_RUN};
+pub use queue::{ByteQueue, QueueConsumer, QueueProducer};
+#[cfg(feature = "std")]
+pub use ring::RingDecoder;
+#[cfg(feature = "test-support")]
+pub use selftest::self_test;
+pub use slice::SliceDecoder;
+pub use stats::DecoderStats;
+#[cfg(feature = "std")]
+pub use stream::FrameStream;
 pub use generic_array::typenum;
 
-pub struct BinCrc<N: ArrayLength<u8>> {
-    buffer: GenericArray<u8, N>,
-    read_idx: usize,
-    write_idx: usize,
-    bytes_left: usize
-}
-
-impl<N: generic_array::ArrayLength<u8>> BinCrc<N> {
-    pub fn new() -> Self {
-        BinCrc {
-            buffer: GenericArray::default(),
-            read_idx: 0, write_idx: 0, bytes_left: 0
-        }
-    }
-
-    pub fn eat_byte<F>(&mut self, byte: u8, f: &mut F)
-        where F: FnMut(&[u8])
-    {
-        //rprintln!("\n\neat: {:02x}", byte);
-        let mut bytes_pending = self.write_idx - self.read_idx;
-        // Incoming frame is larger than the buffer
-        if bytes_pending >= N::to_usize() {
-            self.write_idx = 1;
-            self.read_idx = 0;
-            self.bytes_left = 0;
-            self.buffer[0] = byte;
-            return;
-        }
-        // Move part of the frame in the tail to the head (only when frame is wrapping around after junk bytes)
-        if self.write_idx >= N::to_usize() {
-            unsafe {
-                core::ptr::copy(
-                    self.buffer.as_ptr().offset(self.read_idx as isize),
-                    self.buffer.as_mut_ptr(),
-                    bytes_pending);
-            }
-            self.read_idx = 0;
-            self.write_idx = bytes_pending;
-        }
-        // Save incoming byte
-        self.buffer[self.write_idx] = byte;
-        self.write_idx += 1;
-        bytes_pending += 1;
-        // Valid frame boundary is potentially found, return till enough bytes arrive to make progress
-        if self.bytes_left > 1 {
-            self.bytes_left -= 1;
-            return;
-        }
-        // Search for frame boundary when unsynchronised or just check crc and emit valid frames
-        let mut lookahead_len = bytes_pending;
-        for _ in self.read_idx..self.read_idx + bytes_pending {
-            //rprintln!("___");
-            let result = self.decode_frame(lookahead_len);
-            match result {
-                DecodeResult::NeedMoreBytes => { return; }, // probably wrong if junk was recognized as frame start and followed by a good frame
-                DecodeResult::InvalidData => {
-                    self.read_idx += 1;
-                    lookahead_len -= 1;
-                },
-                DecodeResult::Consumed(count, range) => {
-                    f(&self.buffer[range]);
-                    lookahead_len -= count;
-                    self.read_idx += count;
-                },
-            }
-        }
-
-        if bytes_pending == 0 {
-            self.read_idx = 0;
-            self.write_idx = 0;
-        }
-    }
-
-    fn decode_frame(&mut self, data_len: usize) -> DecodeResult
-    {
-        use DecodeResult::*;
-        // Need at least 1 byte
-        if data_len == 0 {
-            self.bytes_left = 1;
-            //rprintln!("T2");
-            return NeedMoreBytes;
-        } else {
-            self.bytes_left = 0;
-        }
-        // Check start byte
-        let b0 = self.buffer[self.read_idx];
-        let is_len_8b = b0 == 2;
-        let is_len_16b = b0 == 3;
-        let is_len_24b = b0 == 4;
-        if !is_len_8b && !is_len_16b && !is_len_24b {
-            //rprintln!("T3");
-            return InvalidData;
-        }
-        // Ignore too big frames right away
-        if is_len_24b {
-            //rprintln!("T4");
-            return InvalidData;
-        }
-        // Not enough bytes to determine length
-        if data_len < b0 as usize {
-            self.bytes_left = b0 as usize - data_len;
-            //rprintln!("T5");
-            return NeedMoreBytes;
-        }
-        let frame_len = if is_len_8b {
-            let len = self.buffer[self.read_idx + 1];
-            if len == 0 {
-                //rprintln!("T6");
-                return InvalidData;
-            }
-            len as usize
-        } else { // 16b
-            let beu16: [u8; 2] = self.buffer[self.read_idx + 1 ..= self.read_idx + 2].try_into().unwrap();
-            let len = u16::from_be_bytes(beu16);
-            if len < 255 {
-                //rprintln!("T7");
-                return InvalidData;
-            }
-            len as usize
-        };
-        //rprintln!("frame_len: {}", frame_len);
-        // Ignore too big frames
-        if frame_len > N::to_usize() {
-            //rprintln!("T8");
-            return InvalidData;
-        }
-        // Rest of the frame
-        if data_len < frame_len + b0 as usize + 3 {
-            self.bytes_left = frame_len + b0 as usize + 3 - data_len;
-            //rprintln!("T9");
-            return NeedMoreBytes;
-        }
-        // Invalid stop byte
-        if self.buffer[self.read_idx + b0 as usize + frame_len + 2] != 3 {
-            //rprintln!("T10");
-            return InvalidData;
-        }
-        // Check CRC
-        let received_crc: [u8; 2] = self.buffer[
-            self.read_idx + frame_len + b0 as usize ..=
-                self.read_idx + frame_len + b0 as usize + 1
-            ].try_into().unwrap();
-        let received_crc = u16::from_be_bytes(received_crc);
-        let crc = crc16::State::<crc16::XMODEM>::calculate(
-            &self.buffer[self.read_idx + b0 as usize .. self.read_idx + b0 as usize + frame_len]
-        );
-        if crc == received_crc {
-            //rprintln!("vesc_valid");
-            Consumed(
-                frame_len + b0 as usize + 3,
-                Range{
-                    start: self.read_idx + b0 as usize,
-                    end: self.read_idx + b0 as usize + frame_len
-                }
-            )
-        } else {
-            //rprintln!("crc r:{:04x} c:{:04x}", received_crc, crc);
-            InvalidData
-        }
-    }
-
-    pub fn size_hint(frame_len: usize) -> Result<usize, BinCrcError> {
-        if frame_len <= 255 {
-            Ok(2 + frame_len + 3)
-        } else if frame_len >= 256 && frame_len <= 512 {
-            Ok(3 + frame_len + 3)
-        } else {
-            Err(BinCrcError::TooBig)
-        }
-    }
-
-    pub fn commit_frame(
-        frame: &[u8],
-        buf: &mut[u8]
-    ) -> core::result::Result<(), BinCrcError>
-    {
-        let (bytes_required, first_byte) = if frame.len() <= 255 {
-            (2 + frame.len() + 3, 2u8)
-        } else if frame.len() >= 256 && frame.len() <= N::to_usize() {
-            (3 + frame.len() + 3, 3u8)
-        } else {
-            return Err(BinCrcError::InvalidLength);
-        };
-        if buf.len() < bytes_required {
-            return Err(BinCrcError::NotEnoughSpace);
-        }
-        buf[0] = first_byte;
-        if frame.len() <= 255 {
-            buf[1] = frame.len() as u8;
-        } else {
-            let lenbe: [u8; 2] = (frame.len() as u16).to_be_bytes();
-            buf[1] = lenbe[0];
-            buf[2] = lenbe[1];
-        }
-        let data_start_idx = first_byte as usize;
-        buf[data_start_idx .. data_start_idx + frame.len()].copy_from_slice(frame);
-        let crc: u16 = crc16::State::<crc16::XMODEM>::calculate(frame);
-        let crc_start_idx = data_start_idx + frame.len();
-        buf[crc_start_idx ..= crc_start_idx + 1].copy_from_slice(&crc.to_be_bytes());
-        buf[crc_start_idx + 2] = 3;
-        Ok(())
-    }
-}
-
-enum DecodeResult {
-    NeedMoreBytes,
-    InvalidData,
-    Consumed(usize, Range<usize>)
-}
+/// Decoder that also encodes, kept for compatibility and for tokio's `Framed`, which needs
+/// one type implementing both `Encoder` and `Decoder`.
+pub type BinCrc<N, L = DynLen> = BinCrcDecoder<N, L>;
 
 #[derive(Debug)]
 pub enum BinCrcError {
     InvalidLength,
-    NotEnoughSpace,
+    /// Output buffer holds `available` bytes, but `required` are needed, for everything
+    /// that was to be written in the call
+    NotEnoughSpace { required: usize, available: usize },
+    /// Frame is longer than the buffer it has to fit into
     TooBig,
+    /// All `MAX_HANDLERS` handler slots are taken
+    TooManyHandlers,
+    /// Frame doesn't begin with a known start byte
+    BadStart,
+    /// Length field is out of range or doesn't match the amount of data
+    BadLength,
+    /// Stop byte is missing
+    BadStop,
+    CrcMismatch,
+    /// CRC is fine, but the secondary checksum is not, see `BinCrcConfig::checksum`
+    ChecksumMismatch,
+    /// Input ended in the middle of a frame
+    UnexpectedEof,
+    /// Text frame is not valid UTF-8
+    InvalidUtf8,
+    /// Chunks of a message were lost, see `Reassembler`
+    IncompleteMessage,
+    /// Payload isn't a whole number of 16 bit words, see `LengthUnit::Words16`
+    OddLength,
+    /// `self_test` got a payload of this length back wrong or not at all
+    SelfTestFailed { payload_len: usize },
     #[cfg(feature = "std")]
     Io(std::io::Error)
 }
@@ -236,47 +103,3 @@ impl From<std::io::Error> for BinCrcError {
         BinCrcError::Io(io_err)
     }
 }
-
-#[cfg(feature = "std")]
-impl<N: generic_array::ArrayLength<u8>> Decoder for BinCrc<N> {
-    type Item = Vec<Vec<u8>>;
-    type Error = BinCrcError;
-
-    fn decode(&mut self, acc: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        if !acc.is_empty() {
-            let mut frames = Vec::new();
-            for b in acc.iter() {
-                self.eat_byte(*b, &mut |frame| {
-                    frames.push(Vec::from(frame));
-                });
-            }
-            acc.clear();
-            Ok(Some(frames))
-        } else {
-            Ok(None)
-        }
-    }
-}
-
-#[cfg(feature = "std")]
-impl<N: generic_array::ArrayLength<u8>> Encoder for BinCrc<N> {
-    type Item = Vec<u8>;
-    type Error = BinCrcError;
-
-    fn encode(&mut self, item: Self::Item, buf: &mut BytesMut) -> Result<(), Self::Error> {
-        let size_hint = BinCrc::<N>::size_hint(item.len())?;
-        let mut vec = Vec::new();
-        vec.resize(size_hint, 0);
-        BinCrc::<N>::commit_frame(item.as_slice(), vec.as_mut_slice())?;
-        buf.put_slice(vec.as_slice());
-        Ok(())
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
-    }
-}
@@ -0,0 +1,156 @@
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::future::poll_fn;
+use std::io;
+
+use futures_core::Stream;
+use generic_array::ArrayLength;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{BinCrcDecoder, BinCrcError, DynLen, FrameStream, LenField};
+
+/// Framed link over a serial port, created with `BinCrcDecoder::into_link`.
+///
+/// Works with anything that is `AsyncRead + AsyncWrite`, `tokio_serial`'s serial stream being
+/// the usual one. Frames failing CRC are yielded as `BinCrcError::CrcMismatch` and the stream
+/// goes on, they are consumed as a whole the way `eat_byte_with_suspect` does. Otherwise it reads
+/// the same way as `FrameStream`, EOF and I/O errors end the frames.
+///
+/// ```ignore
+/// let port = tokio_serial::Serial::from_path("/dev/ttyUSB0", &Default::default())?;
+/// let mut link = BinCrc::<U512>::new().into_link(port);
+/// link.send(&[1, 2, 3]).await?;
+/// while let Some(frame) = link.frames().next().await {
+///     match frame {
+///         Ok(payload) => println!("{:?}", payload),
+///         Err(BinCrcError::CrcMismatch) => continue,
+///         Err(e) => return Err(e)
+///     }
+/// }
+/// ```
+pub struct SerialLink<N: ArrayLength<u8>, T, L: LenField = DynLen> {
+    stream: FrameStream<N, T, L>
+}
+
+impl<N: ArrayLength<u8>, L: LenField> BinCrcDecoder<N, L> {
+    pub fn into_link<T: AsyncRead + AsyncWrite + Unpin>(self, io: T) -> SerialLink<N, T, L> {
+        let mut stream = self.into_stream(io);
+        stream.yield_suspects();
+        SerialLink { stream }
+    }
+}
+
+impl<N: ArrayLength<u8>, T: AsyncRead + AsyncWrite + Unpin, L: LenField> SerialLink<N, T, L> {
+    /// Frames received, see `SerialLink` for how errors are yielded.
+    pub fn frames(&mut self) -> Frames<'_, N, T, L> {
+        Frames { link: self }
+    }
+
+    /// Frames `payload` with the decoder config and writes it out, flushing afterwards.
+    pub async fn send(&mut self, payload: &[u8]) -> Result<(), BinCrcError> {
+        let frame = BinCrcDecoder::<N, L>::frame_with(self.stream.decoder().config(), payload)?;
+        let mut written = 0;
+        while written < frame.len() {
+            let io = self.stream.get_mut();
+            let count = poll_fn(|cx| Pin::new(&mut *io).poll_write(cx, &frame[written..])).await?;
+            if count == 0 {
+                return Err(io::Error::from(io::ErrorKind::WriteZero).into());
+            }
+            written += count;
+        }
+        let io = self.stream.get_mut();
+        poll_fn(|cx| Pin::new(&mut *io).poll_flush(cx)).await?;
+        Ok(())
+    }
+
+    pub fn decoder(&self) -> &BinCrcDecoder<N, L> {
+        self.stream.decoder()
+    }
+
+    pub fn get_ref(&self) -> &T {
+        self.stream.get_ref()
+    }
+
+    /// Frames received but not taken out with `frames` yet are dropped.
+    pub fn into_inner(self) -> (BinCrcDecoder<N, L>, T) {
+        self.stream.into_inner()
+    }
+}
+
+/// Stream of frames received over a `SerialLink`.
+pub struct Frames<'l, N: ArrayLength<u8>, T, L: LenField = DynLen> {
+    link: &'l mut SerialLink<N, T, L>
+}
+
+impl<'l, N: ArrayLength<u8>, T: AsyncRead + AsyncWrite + Unpin, L: LenField> Stream for Frames<'l, N, T, L> {
+    type Item = Result<Vec<u8>, BinCrcError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.get_mut().link.stream).poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::U64;
+    use std::future::Future;
+    use std::task::Waker;
+
+    // Serial port stand-in, reads from rx and collects what is written
+    struct Port {
+        rx: Vec<u8>,
+        tx: Vec<u8>
+    }
+
+    impl AsyncRead for Port {
+        fn poll_read(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            let len = buf.len().min(this.rx.len());
+            buf[..len].copy_from_slice(&this.rx[..len]);
+            this.rx.drain(..len);
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    impl AsyncWrite for Port {
+        fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            // Short writes, so that send has to carry on
+            let len = buf.len().min(5);
+            self.get_mut().tx.extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn frames_and_send() {
+        let mut bad = BinCrc::<U64>::frame([3; 8]).unwrap();
+        bad[4] ^= 1;
+        let rx = [BinCrc::<U64>::frame([1; 8]).unwrap(), bad, BinCrc::<U64>::frame([2; 50]).unwrap()].concat();
+        let mut link = BinCrc::<U64>::new().into_link(Port { rx, tx: Vec::new() });
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut items = Vec::new();
+        while let Poll::Ready(Some(item)) = Pin::new(&mut link.frames()).poll_next(&mut cx) {
+            items.push(item);
+        }
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].as_ref().unwrap(), &[1; 8]);
+        assert!(matches!(items[1], Err(BinCrcError::CrcMismatch)));
+        assert_eq!(items[2].as_ref().unwrap(), &[2; 50]);
+
+        let mut link = BinCrc::<U64>::new().into_link(Port { rx: Vec::new(), tx: Vec::new() });
+        let send = link.send(&[9; 20]);
+        assert!(matches!(std::pin::pin!(send).poll(&mut cx), Poll::Ready(Ok(()))));
+        assert_eq!(link.get_ref().tx, BinCrc::<U64>::frame([9; 20]).unwrap());
+    }
+}
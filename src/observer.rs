@@ -0,0 +1,45 @@
+use crate::BinCrcError;
+
+/// Frames in a row failing CRC, with no valid one in between, that fire `SyncObserver::crc_failure_run`.
+pub const CRC_FAILURE_RUN: usize = 8;
+
+/// Link quality events, fired from inside `eat_byte*` and `Decoder::decode` calls.
+pub trait SyncObserver: Sync {
+    /// First valid frame after `skipped_bytes` junk bytes were dropped.
+    fn resynced(&self, skipped_bytes: usize);
+
+    /// More than `BinCrcConfig::max_junk_run` junk bytes in a row were dropped, fired once per run.
+    fn gap_exceeded(&self, _junk_run: usize) {}
+
+    /// Frame was dropped for `CrcMismatch`, or for `TooBig` when it can't fit into the buffer.
+    /// Frames failing CRC that are handed to `eat_byte_with_suspect` don't count as dropped.
+    fn frame_dropped(&self, _error: BinCrcError) {}
+
+    /// `CRC_FAILURE_RUN` frames in a row failed CRC, fired once per run. Framing that is fine
+    /// while every CRC is wrong usually means the other end uses a different CRC, see
+    /// `BinCrcDecoder::config_matches`.
+    fn crc_failure_run(&self, _failed_frames: usize) {}
+
+    /// Buffer filled up without a frame completing and `OversizeRecovery::Reset` dropped the
+    /// `discarded` bytes in it at once. Usually `N` is too small for the frames on the link, or
+    /// the link is badly out of sync.
+    fn buffer_overflow(&self, _discarded: &[u8]) {}
+}
+
+/// Decoder diagnostics handed to `BinCrcDecoder::set_event_handler`, fired from inside `eat_byte*`
+/// and `Decoder::decode` calls alongside the `SyncObserver` ones. Without a handler nothing is
+/// built, decoding is the same as without events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecoderEvent {
+    /// `count` bytes that aren't part of any frame were dropped.
+    JunkBytes { count: usize },
+    /// Frame failed CRC: `expected` was calculated over it, `got` was received.
+    CrcError { expected: u32, got: u32 },
+    /// First valid frame after `dropped` junk bytes.
+    Resynced { dropped: usize },
+    /// Buffer filled up and `OversizeRecovery::Reset` dropped the `discarded` bytes in it.
+    BufferOverflow { discarded: usize },
+    /// Frame can't fit into the buffer. `claimed_len` is the payload length from its header, or
+    /// the whole frame length a `FrameHandler` asked for.
+    OversizedFrame { claimed_len: usize }
+}
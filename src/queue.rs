@@ -0,0 +1,177 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use generic_array::{ArrayLength, GenericArray};
+
+/// Lock-free queue of `N` bytes for receiving in an interrupt and decoding in the main loop.
+///
+/// `split` hands out one producer and one consumer, which is the whole concurrency contract:
+/// a single context pushes bytes with `QueueProducer::push_byte`, a single other context takes
+/// them out with `BinCrcDecoder::process`. Neither side blocks or waits for the other, and
+/// `push_byte` takes constant time, so it is fine to call from an interrupt handler.
+pub struct ByteQueue<N: ArrayLength<u8>> {
+    buffer: UnsafeCell<GenericArray<u8, N>>,
+    // Bytes ever pushed modulo 2 * N, only stored by the producer
+    head: AtomicUsize,
+    // Bytes ever taken out modulo 2 * N, only stored by the consumer
+    tail: AtomicUsize
+}
+
+// Producer and consumer never touch the same byte at the same time, see push_byte and peek
+unsafe impl<N: ArrayLength<u8>> Sync for ByteQueue<N> {}
+
+impl<N: ArrayLength<u8>> Default for ByteQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: ArrayLength<u8>> ByteQueue<N> {
+    pub fn new() -> Self {
+        ByteQueue {
+            buffer: UnsafeCell::new(GenericArray::default()),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0)
+        }
+    }
+
+    pub fn split(&mut self) -> (QueueProducer<'_, N>, QueueConsumer<'_, N>) {
+        let queue = &*self;
+        (QueueProducer { queue }, QueueConsumer { queue })
+    }
+
+    // Counters wrap at 2 * N rather than at usize::MAX, where the slot index would jump unless N
+    // is a power of two. Twice N still tells a full queue from an empty one.
+    fn advance(counter: usize, count: usize) -> usize {
+        (counter + count) % (2 * N::to_usize())
+    }
+
+    fn distance(head: usize, tail: usize) -> usize {
+        (head + 2 * N::to_usize() - tail) % (2 * N::to_usize())
+    }
+
+    // Bytes are accessed through a raw pointer only, the two sides never borrow the whole buffer
+    fn slots(&self) -> *mut u8 {
+        self.buffer.get() as *mut u8
+    }
+}
+
+/// Pushing half of a `ByteQueue`.
+pub struct QueueProducer<'q, N: ArrayLength<u8>> {
+    queue: &'q ByteQueue<N>
+}
+
+impl<'q, N: ArrayLength<u8>> QueueProducer<'q, N> {
+    /// Queues `byte`, returns `false` if the queue is full and the byte is lost.
+    pub fn push_byte(&mut self, byte: u8) -> bool {
+        let head = self.queue.head.load(Ordering::Relaxed);
+        let tail = self.queue.tail.load(Ordering::Acquire);
+        if ByteQueue::<N>::distance(head, tail) == N::to_usize() {
+            return false;
+        }
+        // The consumer doesn't read this slot until head is stored below
+        unsafe {
+            self.queue.slots().add(head % N::to_usize()).write(byte);
+        }
+        self.queue.head.store(ByteQueue::<N>::advance(head, 1), Ordering::Release);
+        true
+    }
+}
+
+/// Taking half of a `ByteQueue`, see `BinCrcDecoder::process`.
+pub struct QueueConsumer<'q, N: ArrayLength<u8>> {
+    queue: &'q ByteQueue<N>
+}
+
+impl<'q, N: ArrayLength<u8>> QueueConsumer<'q, N> {
+    /// Number of bytes queued.
+    pub fn len(&self) -> usize {
+        let head = self.queue.head.load(Ordering::Acquire);
+        ByteQueue::<N>::distance(head, self.queue.tail.load(Ordering::Relaxed))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Oldest queued bytes up to the end of the buffer, empty if there are none
+    pub(crate) fn peek(&self) -> &[u8] {
+        let len = self.len();
+        let start = self.queue.tail.load(Ordering::Relaxed) % N::to_usize();
+        let end = (start + len).min(N::to_usize());
+        // The producer doesn't write these slots until tail is moved past them in consume
+        unsafe { core::slice::from_raw_parts(self.queue.slots().add(start), end - start) }
+    }
+
+    pub(crate) fn consume(&mut self, count: usize) {
+        let tail = self.queue.tail.load(Ordering::Relaxed);
+        self.queue.tail.store(ByteQueue::<N>::advance(tail, count), Ordering::Release);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::{U12, U16, U64};
+
+    #[test]
+    fn interrupt_and_main_loop() {
+        let frames: Vec<Vec<u8>> = (0..200u8).map(|i| vec![i; 1 + i as usize % 20]).collect();
+        let stream: Vec<u8> = frames.iter().flat_map(|f| BinCrc::<U64>::frame(f).unwrap()).collect();
+        let mut queue = ByteQueue::<U16>::new();
+        let (mut producer, mut consumer) = queue.split();
+        let mut decoded = Vec::new();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for b in &stream {
+                    while !producer.push_byte(*b) {
+                        std::thread::yield_now();
+                    }
+                }
+            });
+            let mut dec = BinCrc::<U64>::new();
+            while decoded.len() < frames.len() {
+                dec.process(&mut consumer, &mut |f| decoded.push(f.to_vec()));
+            }
+        });
+        assert_eq!(decoded, frames);
+        assert!(consumer.is_empty());
+    }
+
+    #[test]
+    fn full_queue() {
+        let mut queue = ByteQueue::<U16>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert!((0..16).all(|b| producer.push_byte(b)));
+        assert!(!producer.push_byte(16));
+        assert_eq!(consumer.len(), 16);
+        consumer.consume(10);
+        assert!((16..26).all(|b| producer.push_byte(b)));
+        assert_eq!(consumer.peek(), &[10, 11, 12, 13, 14, 15]);
+        consumer.consume(6);
+        assert_eq!(consumer.peek(), &(16..26).collect::<Vec<u8>>()[..]);
+    }
+
+    #[test]
+    fn counters_wrap() {
+        // Not a power of two, the slot index must not jump when the counters wrap
+        let mut queue = ByteQueue::<U12>::new();
+        let (mut producer, mut consumer) = queue.split();
+        let mut next = 0u8;
+        for i in 0..1000 {
+            let pushed: Vec<u8> = (0..7).map(|j| next.wrapping_add(j)).collect();
+            assert!(pushed.iter().all(|b| producer.push_byte(*b)));
+            next = next.wrapping_add(7);
+            let mut taken = Vec::new();
+            while !consumer.is_empty() {
+                let chunk = consumer.peek().to_vec();
+                consumer.consume(chunk.len());
+                taken.extend(chunk);
+            }
+            assert_eq!(taken, pushed, "round {}", i);
+        }
+        assert!(queue.head.load(Ordering::Relaxed) < 24);
+        assert!(queue.tail.load(Ordering::Relaxed) < 24);
+    }
+}
@@ -0,0 +1,112 @@
+use core::marker::PhantomData;
+use std::collections::VecDeque;
+
+use generic_array::ArrayLength;
+use generic_array::typenum::Unsigned;
+
+use crate::{BinCrcDecoder, DynLen, LenField};
+
+/// Decoder keeping the latest `DEPTH` frames, each new frame overwrites the oldest one once
+/// `DEPTH` are kept, for displays only interested in the most recent state. See
+/// `BufferedDecoder` for a queue that drops new frames instead.
+pub struct RingDecoder<N: ArrayLength<u8>, DEPTH: Unsigned, L: LenField = DynLen> {
+    decoder: BinCrcDecoder<N, L>,
+    frames: VecDeque<Vec<u8>>,
+    frames_overwritten: u64,
+    _depth: PhantomData<DEPTH>
+}
+
+impl<N: ArrayLength<u8>, DEPTH: Unsigned, L: LenField> RingDecoder<N, DEPTH, L> {
+    pub fn new(decoder: BinCrcDecoder<N, L>) -> Self {
+        RingDecoder {
+            decoder,
+            frames: VecDeque::with_capacity(DEPTH::USIZE),
+            frames_overwritten: 0,
+            _depth: PhantomData
+        }
+    }
+
+    pub fn eat_byte(&mut self, byte: u8) {
+        self.eat_bytes(&[byte]);
+    }
+
+    pub fn eat_bytes(&mut self, bytes: &[u8]) {
+        let (frames, overwritten) = (&mut self.frames, &mut self.frames_overwritten);
+        self.decoder.eat_bytes(bytes, &mut |f| {
+            if DEPTH::USIZE == 0 {
+                *overwritten += 1;
+                return;
+            }
+            // Oldest frame's allocation is reused for the new one
+            let mut frame = if frames.len() == DEPTH::USIZE {
+                *overwritten += 1;
+                frames.pop_front().unwrap()
+            } else {
+                Vec::new()
+            };
+            frame.clear();
+            frame.extend_from_slice(f);
+            frames.push_back(frame);
+        });
+    }
+
+    /// Frames kept, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        self.frames.iter().map(|f| f.as_slice())
+    }
+
+    /// Most recent frame.
+    pub fn latest(&self) -> Option<&[u8]> {
+        self.frames.back().map(|f| f.as_slice())
+    }
+
+    /// Number of frames kept, at most `DEPTH`.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Frames dropped to make room for newer ones.
+    pub fn frames_overwritten(&self) -> u64 {
+        self.frames_overwritten
+    }
+
+    pub fn decoder(&self) -> &BinCrcDecoder<N, L> {
+        &self.decoder
+    }
+
+    /// Frames kept are dropped.
+    pub fn into_inner(self) -> BinCrcDecoder<N, L> {
+        self.decoder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::{U3, U64};
+
+    #[test]
+    fn latest_frames() {
+        let stream: Vec<u8> = (1..=5u8).flat_map(|i| BinCrc::<U64>::frame([i; 4]).unwrap()).collect();
+        let mut ring = RingDecoder::<U64, U3>::new(BinCrc::new());
+        ring.eat_bytes(&stream[..18]);
+        assert_eq!(ring.iter().collect::<Vec<_>>(), vec![&[1; 4], &[2; 4]]);
+        for b in &stream[18..] {
+            ring.eat_byte(*b);
+        }
+        assert_eq!(ring.iter().collect::<Vec<_>>(), vec![&[3; 4], &[4; 4], &[5; 4]]);
+        assert_eq!(ring.latest(), Some(&[5; 4][..]));
+        assert_eq!(ring.frames_overwritten(), 2);
+        ring.clear();
+        assert!(ring.is_empty());
+    }
+}
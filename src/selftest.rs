@@ -0,0 +1,45 @@
+use crate::typenum::U320;
+use crate::{BinCrcDecoder, BinCrcError};
+
+// Covers both length widths and the 8/16 bit crossover
+#[cfg(not(feature = "len8-only"))]
+const PAYLOAD_LENS: &[usize] = &[1, 16, 254, 255, 256, 300];
+// Up to the largest 8 bit length, nothing longer can be framed
+#[cfg(feature = "len8-only")]
+const PAYLOAD_LENS: &[usize] = &[1, 16, 254, 255];
+
+/// Encodes a few known payloads, feeds them back through a decoder and checks that every one
+/// comes out intact. Returns `SelfTestFailed` with the offending payload length otherwise.
+pub fn self_test() -> Result<(), BinCrcError> {
+    let mut payload = [0u8; 300];
+    payload.iter_mut().enumerate().for_each(|(i, b)| *b = (i * 7) as u8);
+    let mut wire = [0u8; 320];
+    let mut decoder = BinCrcDecoder::<U320>::new();
+    for &len in PAYLOAD_LENS.iter() {
+        let payload = &payload[..len];
+        let wire_len = BinCrcDecoder::<U320>::size_hint(len)?;
+        BinCrcDecoder::<U320>::commit_frame(payload, &mut wire[..wire_len])?;
+        let mut decoded = 0;
+        let mut intact = true;
+        for b in &wire[..wire_len] {
+            decoder.eat_byte(*b, &mut |frame| {
+                decoded += 1;
+                intact &= frame == payload;
+            });
+        }
+        if decoded != 1 || !intact {
+            return Err(BinCrcError::SelfTestFailed { payload_len: len });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn self_test_passes() {
+        assert!(self_test().is_ok());
+    }
+}
@@ -0,0 +1,189 @@
+use core::marker::PhantomData;
+use core::ops::Range;
+
+use crate::{delimited, frame, BinCrcConfig, DecoderStats, DynLen, Framing, LenField};
+use crate::frame::Parsed;
+
+/// Streaming decoder reassembling frames in a buffer it borrows, e.g. a DMA buffer, instead of
+/// one of a fixed size `N`. Frames borrow from that buffer for its whole lifetime, so they can
+/// be kept around after more bytes are eaten, without a copy.
+///
+/// The part of the buffer holding an emitted frame is given away with it, so the buffer
+/// shrinks by every frame. Junk and frames failing CRC don't use it up. Once `remaining` is
+/// too small for the frames expected, continue in a fresh buffer with `set_buffer`. A frame
+/// longer than `remaining` is dropped and counted as oversize.
+///
+/// Handlers, observers and the resync options of `BinCrcDecoder` are not supported, junk is
+/// skipped one byte at a time.
+pub struct SliceDecoder<'a, L: LenField = DynLen> {
+    // Part of the buffer not given away yet
+    rest: &'a mut [u8],
+    // Bytes received but not decoded yet, within rest
+    pending: Range<usize>,
+    config: BinCrcConfig,
+    stats: DecoderStats,
+    _len: PhantomData<L>
+}
+
+impl<'a, L: LenField> SliceDecoder<'a, L> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self::with_config(buffer, BinCrcConfig::default())
+    }
+
+    /// `config.length_width` is replaced with the one `L` dictates, unless it is `DynLen`.
+    pub fn with_config(buffer: &'a mut [u8], config: BinCrcConfig) -> Self {
+        SliceDecoder { rest: buffer, pending: 0..0, config: L::config(&config), stats: DecoderStats::default(), _len: PhantomData }
+    }
+
+    pub fn config(&self) -> &BinCrcConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> &DecoderStats {
+        &self.stats
+    }
+
+    /// Bytes of the buffer not given away to frames yet, longest frame that can still be decoded.
+    pub fn remaining(&self) -> usize {
+        self.rest.len()
+    }
+
+    /// Continues in `buffer`, a partly received frame is moved over if it fits and dropped otherwise.
+    pub fn set_buffer(&mut self, buffer: &'a mut [u8]) {
+        let len = self.pending.len();
+        if len <= buffer.len() {
+            buffer[..len].copy_from_slice(&self.rest[self.pending.clone()]);
+            self.pending = 0..len;
+        } else {
+            self.stats.junk_bytes += len as u64;
+            self.pending = 0..0;
+        }
+        self.rest = buffer;
+    }
+
+    pub fn eat_byte<F>(&mut self, byte: u8, f: &mut F)
+        where F: FnMut(&'a [u8])
+    {
+        if self.rest.is_empty() {
+            self.stats.junk_bytes += 1;
+            return;
+        }
+        if self.pending.end == self.rest.len() {
+            // Only after set_buffer with a smaller buffer, no frame fits with the oldest byte
+            if self.pending.start == 0 {
+                self.pending.start = 1;
+                self.stats.junk_bytes += 1;
+            }
+            self.compact();
+        }
+        self.rest[self.pending.end] = byte;
+        self.pending.end += 1;
+        self.scan(f);
+    }
+
+    pub fn eat_bytes<F>(&mut self, bytes: &[u8], f: &mut F)
+        where F: FnMut(&'a [u8])
+    {
+        for b in bytes {
+            self.eat_byte(*b, f);
+        }
+    }
+
+    // Moves pending bytes to the front, so that skipped junk is not given away with a frame
+    fn compact(&mut self) {
+        let len = self.pending.len();
+        self.rest.copy_within(self.pending.clone(), 0);
+        self.pending = 0..len;
+    }
+
+    fn dropped(&mut self, count: usize) {
+        self.pending.start += count;
+        self.stats.junk_bytes += count as u64;
+    }
+
+    fn scan<F>(&mut self, f: &mut F)
+        where F: FnMut(&'a [u8])
+    {
+        loop {
+            match frame::parse::<L>(&self.config, self.rest.len(), &self.rest[self.pending.clone()]) {
+                Parsed::NeedMoreBytes(_) => { break; },
+                Parsed::Frame { count, range } => {
+                    self.compact();
+                    let (frame, rest) = core::mem::take(&mut self.rest).split_at_mut(count);
+                    self.rest = rest;
+                    self.pending = 0..self.pending.end - count;
+                    let range = if self.config.framing == Framing::Delimited {
+                        delimited::unescape_in_place(frame, range)
+                    } else {
+                        range
+                    };
+                    let frame: &'a [u8] = frame;
+                    self.stats.frames += 1;
+                    f(&frame[range]);
+                },
+                Parsed::CrcMismatch { count, .. } => {
+                    self.stats.crc_errors += 1;
+                    self.dropped(if self.config.crc_error_skips_frame { count } else { 1 });
+                },
+                Parsed::TooBig { .. } => {
+                    self.stats.oversize_frames += 1;
+                    self.dropped(1);
+                },
+                Parsed::Invalid(_) => { self.dropped(1); }
+            }
+        }
+        if self.pending.is_empty() {
+            self.pending = 0..0;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::U64;
+
+    #[test]
+    fn frames_borrow_buffer() {
+        let frames: Vec<Vec<u8>> = (1..=4u8).map(|i| vec![i; i as usize * 3]).collect();
+        let mut stream = vec![0xAA, 2, 0x55];
+        for frame in &frames {
+            stream.extend(BinCrc::<U64>::frame(frame).unwrap());
+        }
+        let mut buffer = [0; 64];
+        let mut decoded: Vec<&[u8]> = Vec::new();
+        let mut dec = SliceDecoder::<DynLen>::new(&mut buffer);
+        dec.eat_bytes(&stream, &mut |f| decoded.push(f));
+        assert_eq!(decoded, frames);
+        assert_eq!(dec.stats().frames, 4);
+        assert_eq!(dec.stats().junk_bytes, 3);
+        // Junk didn't use the buffer up, every frame did
+        let used: usize = frames.iter().map(|f| BinCrc::<U64>::size_hint(f.len()).unwrap()).sum();
+        assert_eq!(dec.remaining(), 64 - used);
+    }
+
+    #[test]
+    fn buffer_runs_out() {
+        let framed = BinCrc::<U64>::frame([7; 20]).unwrap();
+        let mut first = [0; 60];
+        let mut second = [0; 40];
+        let mut third = [0; 30];
+        let mut decoded: Vec<&[u8]> = Vec::new();
+        let mut dec = SliceDecoder::<DynLen>::new(&mut first);
+        dec.eat_bytes(&framed, &mut |f| decoded.push(f));
+        dec.eat_bytes(&framed, &mut |f| decoded.push(f));
+        assert!(dec.remaining() < framed.len());
+        // Doesn't fit into what is left
+        dec.eat_bytes(&framed, &mut |f| decoded.push(f));
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(dec.stats().oversize_frames, 1);
+
+        // Partly received frame carries over
+        dec.set_buffer(&mut second);
+        dec.eat_bytes(&framed[..5], &mut |f| decoded.push(f));
+        dec.set_buffer(&mut third);
+        dec.eat_bytes(&framed[5..], &mut |f| decoded.push(f));
+        assert_eq!(decoded, vec![&[7; 20][..]; 3]);
+    }
+}
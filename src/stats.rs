@@ -0,0 +1,29 @@
+/// Running counters kept by the decoder, see `BinCrcDecoder::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DecoderStats {
+    /// Valid frames decoded
+    pub frames: u64,
+    /// Bytes dropped while looking for a valid frame
+    pub junk_bytes: u64,
+    /// Configured idle, preamble and settle bytes skipped between frames
+    pub idle_bytes: u64,
+    /// Frames with valid framing but wrong CRC
+    pub crc_errors: u64,
+    /// Frames found by `Resync::CrcAnchored` that the regular scan missed
+    pub crc_anchored_frames: u64,
+    /// Frames of the above that only passed with one extra byte left out, as a link duplicating
+    /// or inserting bytes produces
+    pub insertions_skipped: u64,
+    /// Frames dropped as an echo of what was sent, see `expect_echo`
+    pub echoes_suppressed: u64,
+    /// Headers of frames that can't fit into the buffer, a steady count means `N` is too small.
+    /// Junk can look like such a header too, so an occasional one is expected on a noisy link.
+    pub oversize_frames: u64,
+    /// Frames given up on over `BinCrcConfig::max_partial_bytes`
+    pub partial_frames_abandoned: u64,
+    /// Frames given up on for a valid frame inside them, see `Resync::Backtrack`
+    pub backtracks: u64,
+    /// Payloads too long to be framed that `Encoder::encode` skipped, see `OversizePayload::Skip`.
+    /// Counted here as tokio's `Framed` uses the decoder for encoding as well.
+    pub frames_skipped: u64,
+}
@@ -0,0 +1,149 @@
+use core::cell::RefCell;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use std::collections::VecDeque;
+
+use futures_core::Stream;
+use generic_array::ArrayLength;
+use tokio::io::AsyncRead;
+
+use crate::{BinCrcDecoder, BinCrcError, DynLen, LenField};
+
+const READ_CHUNK: usize = 64;
+
+/// Frames decoded from an `AsyncRead`, created with `BinCrcDecoder::into_stream`.
+///
+/// Ends after the reader reaches EOF, yielding `BinCrcError::UnexpectedEof` first if a frame
+/// was cut short. I/O errors are yielded once and end the stream as well.
+pub struct FrameStream<N: ArrayLength<u8>, R, L: LenField = DynLen> {
+    decoder: BinCrcDecoder<N, L>,
+    reader: R,
+    read_buf: [u8; READ_CHUNK],
+    items: VecDeque<Result<Vec<u8>, BinCrcError>>,
+    // Frames failing CRC are yielded as CrcMismatch instead of being dropped, see SerialLink
+    suspects: bool,
+    done: bool
+}
+
+// Nothing is pinned structurally, the decoder buffer is only accessed through &mut
+impl<N: ArrayLength<u8>, R: Unpin, L: LenField> Unpin for FrameStream<N, R, L> {}
+
+impl<N: ArrayLength<u8>, L: LenField> BinCrcDecoder<N, L> {
+    pub fn into_stream<R: AsyncRead + Unpin>(self, reader: R) -> FrameStream<N, R, L> {
+        FrameStream {
+            decoder: self,
+            reader,
+            read_buf: [0; READ_CHUNK],
+            items: VecDeque::new(),
+            suspects: false,
+            done: false
+        }
+    }
+}
+
+impl<N: ArrayLength<u8>, R, L: LenField> FrameStream<N, R, L> {
+    pub fn into_inner(self) -> (BinCrcDecoder<N, L>, R) {
+        (self.decoder, self.reader)
+    }
+
+    #[cfg(feature = "tokio-serial")]
+    pub(crate) fn yield_suspects(&mut self) {
+        self.suspects = true;
+    }
+
+    pub fn decoder(&self) -> &BinCrcDecoder<N, L> {
+        &self.decoder
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+}
+
+impl<N: ArrayLength<u8>, R: AsyncRead + Unpin, L: LenField> Stream for FrameStream<N, R, L> {
+    type Item = Result<Vec<u8>, BinCrcError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(item) = this.items.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match Pin::new(&mut this.reader).poll_read(cx, &mut this.read_buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e.into())));
+                },
+                Poll::Ready(Ok(0)) => {
+                    this.done = true;
+                    if this.decoder.buffered() != 0 {
+                        return Poll::Ready(Some(Err(BinCrcError::UnexpectedEof)));
+                    }
+                },
+                Poll::Ready(Ok(len)) if this.suspects => {
+                    // Both callbacks queue items, in the order they come in
+                    let items = RefCell::new(&mut this.items);
+                    for b in &this.read_buf[..len] {
+                        this.decoder.eat_byte_with_suspect(
+                            *b,
+                            &mut |f| items.borrow_mut().push_back(Ok(Vec::from(f))),
+                            &mut |_| items.borrow_mut().push_back(Err(BinCrcError::CrcMismatch))
+                        );
+                    }
+                },
+                Poll::Ready(Ok(len)) => {
+                    let items = &mut this.items;
+                    this.decoder.eat_bytes(&this.read_buf[..len], &mut |f| items.push_back(Ok(Vec::from(f))));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinCrc;
+    use crate::typenum::U64;
+    use std::task::Waker;
+
+    fn collect<R: AsyncRead + Unpin>(reader: R) -> Vec<Result<Vec<u8>, BinCrcError>> {
+        let mut stream = BinCrc::<U64>::new().into_stream(reader);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut items = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(item)) => items.push(item),
+                Poll::Ready(None) => return items,
+                Poll::Pending => panic!("slice reader is never pending")
+            }
+        }
+    }
+
+    #[test]
+    fn frames_from_reader() {
+        let mut stream = Vec::new();
+        for i in 0..20u8 {
+            let mut buf = vec![0; BinCrc::<U64>::size_hint(10).unwrap()];
+            BinCrc::<U64>::commit_frame(&[i; 10], &mut buf).unwrap();
+            stream.extend_from_slice(&buf);
+        }
+        let items = collect(&stream[..]);
+        assert_eq!(items.len(), 20);
+        for (i, item) in items.into_iter().enumerate() {
+            assert_eq!(item.unwrap(), vec![i as u8; 10]);
+        }
+
+        let items = collect(&stream[..stream.len() - 2]);
+        assert_eq!(items.len(), 20);
+        assert!(matches!(items.last(), Some(Err(BinCrcError::UnexpectedEof))));
+    }
+}
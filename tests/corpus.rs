@@ -0,0 +1,61 @@
+//! Replays inputs from `tests/corpus` through the decoder. Files are named
+//! `<case>.<frames>.bin`, where `<frames>` is how many frames the default config decodes.
+
+use std::fs;
+use std::path::PathBuf;
+
+use bincrc_codec::{BinCrc, BinCrcConfig, OversizeRecovery, Resync};
+use bincrc_codec::typenum::U64;
+
+fn corpus() -> Vec<(String, usize, Vec<u8>)> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut cases: Vec<_> = fs::read_dir(dir).unwrap().map(|entry| {
+        let path = entry.unwrap().path();
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let (case, frames) = name.rsplit_once('.').expect("file name without a frame count");
+        (case.to_string(), frames.parse().unwrap(), fs::read(&path).unwrap())
+    }).collect();
+    cases.sort();
+    cases
+}
+
+#[test]
+fn replay_corpus() {
+    let cases = corpus();
+    assert!(cases.len() >= 10);
+    for (case, expected, bytes) in &cases {
+        let mut dec = BinCrc::<U64>::new();
+        let mut frames = 0;
+        for b in bytes {
+            dec.eat_byte(*b, &mut |_| frames += 1);
+        }
+        assert_eq!(frames, *expected, "{} byte by byte", case);
+
+        for chunk_len in [1, 7, 64] {
+            let mut dec = BinCrc::<U64>::new();
+            let mut frames = 0;
+            for chunk in bytes.chunks(chunk_len) {
+                dec.eat_bytes(chunk, &mut |_| frames += 1);
+            }
+            assert_eq!(frames, *expected, "{} in chunks of {}", case, chunk_len);
+        }
+    }
+}
+
+#[test]
+fn replay_corpus_other_configs() {
+    let configs = [
+        BinCrcConfig { oversize_recovery: OversizeRecovery::Rescan, ..Default::default() },
+        BinCrcConfig { resync: Resync::CrcAnchored, ..Default::default() },
+        BinCrcConfig { resync: Resync::Backtrack, ..Default::default() },
+        BinCrcConfig { max_partial_bytes: Some(16), stop_byte_not_start: true, ..Default::default() },
+    ];
+    for (case, _, bytes) in &corpus() {
+        for config in &configs {
+            let mut dec = BinCrc::<U64>::with_config(*config);
+            for b in bytes.iter().chain(bytes) {
+                dec.eat_byte(*b, &mut |f| assert!(!f.is_empty(), "{} {:?}", case, config));
+            }
+        }
+    }
+}